@@ -0,0 +1,544 @@
+use std::{
+    num::NonZero,
+    path::{Path, PathBuf},
+};
+
+use crate::{encrypt::EncryptArgs, roster::Roster};
+
+pub enum Args {
+    Rotate(RotateArgs),
+    Reshare(ReshareArgs),
+    Verify(VerifyArgs),
+    Create(CreateArgs),
+    Inspect(InspectArgs),
+}
+
+pub struct RotateArgs {
+    pub recover: RecoverArgs,
+    pub output_file: PathBuf,
+    pub split: SplitArgs,
+}
+
+pub struct ReshareArgs {
+    pub recover: RecoverArgs,
+    pub split: SplitArgs,
+}
+
+pub struct VerifyArgs {
+    pub recover: RecoverArgs,
+}
+
+pub struct CreateArgs {
+    pub output_file: PathBuf,
+    pub split: SplitArgs,
+}
+
+pub struct InspectArgs {
+    pub dkek_file: PathBuf,
+}
+
+// everything needed to decrypt an existing dkek share file
+pub struct RecoverArgs {
+    pub dkek_file: PathBuf,
+    pub shares_required: usize,
+    pub typo_recovery: bool,
+    // read shares from these files instead of asking for them, `-` is stdin
+    pub shares_from: Vec<PathBuf>,
+    // age identity files to decrypt encrypted share files with
+    pub identities: Vec<PathBuf>,
+    // names of the custodians holding the existing shares
+    pub roster: Option<Roster>,
+}
+
+pub struct SplitArgs {
+    pub shares_total: NonZero<usize>,
+    pub shares_required: usize,
+    // write the new shares as json to this file instead of showing them, `-` is stdout
+    pub shares_output: Option<PathBuf>,
+    pub share_encoding: ShareEncoding,
+    // show every share as a qr code as well
+    pub qr: bool,
+    // write a png and an svg qr code of every share into this directory
+    pub qr_output: Option<PathBuf>,
+    // write a printable html card of every share into this directory
+    pub cards_output: Option<PathBuf>,
+    // write every share encrypted to its custodian instead of showing it
+    pub encrypt: Option<EncryptArgs>,
+    // names of the custodians receiving the new shares
+    pub roster: Option<Roster>,
+    // let custodians type in every shown share again
+    pub confirm_shares: bool,
+}
+
+// how the prime and the share values are shown
+#[derive(Clone, Copy)]
+pub enum ShareEncoding {
+    Hex,
+    Words,
+}
+
+fn build_args() -> clap::Command {
+    clap::Command::new("sc-hsm-recrypt")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .args(global_args())
+        .subcommand(
+            clap::Command::new("rotate")
+                .about("re-encrypt the dkek share file with a new password and split it into new shares. all old shares become invalid")
+                .arg(dkek_file_arg())
+                .arg(output_file_arg().help("path to write the re-encrypted dkek share file to"))
+                .arg(old_shares_required_arg())
+                .arg(old_roster_arg())
+                .args(split_args()),
+        )
+        .subcommand(
+            clap::Command::new("reshare")
+                .about("split the existing password of the dkek share file into new shares. all old shares stay valid")
+                .arg(dkek_file_arg())
+                .arg(old_shares_required_arg())
+                .arg(old_roster_arg())
+                .args(split_args()),
+        )
+        .subcommand(
+            clap::Command::new("verify")
+                .about("check that the shares decrypt the dkek share file without generating any new shares")
+                .arg(dkek_file_arg())
+                .arg(shares_required_arg())
+                .arg(roster_arg().help("file with the names of the custodians, one per line in share id order")),
+        )
+        .subcommand(
+            clap::Command::new("create")
+                .about("create a new dkek share file with a random dkek and split its password into shares")
+                .arg(output_file_arg().help("path to write the new dkek share file to"))
+                .args(split_args()),
+        )
+        .subcommand(
+            clap::Command::new("inspect")
+                .about("show information about a dkek share file without decrypting it")
+                .arg(dkek_file_arg()),
+        )
+}
+
+fn dkek_file_arg() -> clap::Arg {
+    clap::Arg::new("file")
+        .required(true)
+        .help("path to the dkek share file")
+        .long("file")
+        .short('f')
+        .value_parser(clap::builder::PathBufValueParser::new())
+}
+
+fn output_file_arg() -> clap::Arg {
+    clap::Arg::new("output")
+        .required(true)
+        .long("output")
+        .short('o')
+        .value_parser(clap::builder::PathBufValueParser::new())
+}
+
+fn shares_required_arg() -> clap::Arg {
+    clap::Arg::new("shares-required")
+        .required(true)
+        .help("minimum required number of shares")
+        .long("shares-required")
+        .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(2..))
+}
+
+fn old_shares_required_arg() -> clap::Arg {
+    clap::Arg::new("old-shares-required")
+        .help("number of shares required by the existing shares (defaults to --shares-required)")
+        .long("old-shares-required")
+        .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(2..))
+}
+
+fn roster_arg() -> clap::Arg {
+    clap::Arg::new("roster")
+        .long("roster")
+        .value_parser(clap::builder::PathBufValueParser::new())
+}
+
+fn old_roster_arg() -> clap::Arg {
+    clap::Arg::new("old-roster")
+        .help("file with the names of the custodians of the existing shares, one per line in share id order")
+        .long("old-roster")
+        .value_parser(clap::builder::PathBufValueParser::new())
+}
+
+// options for entering the existing shares, which can be given before or after the subcommand.
+// only rotate, reshare and verify enter shares, the other subcommands reject them
+const GLOBAL_ARGS: [&str; 3] = ["typo-recovery", "shares-from", "identity"];
+
+fn global_args() -> [clap::Arg; 3] {
+    [
+        clap::Arg::new("typo-recovery")
+            .help("if decrypting fails, search for a single wrong or swapped digit in one of the share values")
+            .long("typo-recovery")
+            .global(true)
+            .action(clap::ArgAction::SetTrue),
+        clap::Arg::new("shares-from")
+            .help("read the shares from a text or json file instead of entering them (`-` for stdin). can be given multiple times")
+            .long("shares-from")
+            .global(true)
+            .action(clap::ArgAction::Append)
+            .value_parser(clap::builder::PathBufValueParser::new()),
+        clap::Arg::new("identity")
+            .help("age identity file to decrypt encrypted share files with. can be given multiple times")
+            .long("identity")
+            .global(true)
+            .action(clap::ArgAction::Append)
+            .value_parser(clap::builder::PathBufValueParser::new()),
+    ]
+}
+
+fn split_args() -> [clap::Arg; 11] {
+    [
+        clap::Arg::new("shares-total")
+            .required(true)
+            .help("total number of shares")
+            .long("shares-total")
+            .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(2..)),
+        shares_required_arg(),
+        clap::Arg::new("shares-output")
+            .help("write the new shares as json to this file instead of showing them (`-` for stdout)")
+            .long("shares-output")
+            .value_parser(clap::builder::PathBufValueParser::new()),
+        clap::Arg::new("share-encoding")
+            .help("show the prime and the share values as hex or as words of the bip39 english wordlist")
+            .long("share-encoding")
+            .value_parser(["hex", "words"])
+            .default_value("hex"),
+        clap::Arg::new("qr")
+            .help("show every share as a qr code as well")
+            .long("qr")
+            .action(clap::ArgAction::SetTrue),
+        clap::Arg::new("qr-output")
+            .help("write every share as a qr code to share-<id>.png and share-<id>.svg in this directory")
+            .long("qr-output")
+            .value_parser(clap::builder::PathBufValueParser::new()),
+        clap::Arg::new("cards-output")
+            .help("write a printable card of every share to card-<id>.html in this directory")
+            .long("cards-output")
+            .value_parser(clap::builder::PathBufValueParser::new()),
+        clap::Arg::new("encrypt-to")
+            .help("recipients file with a `<name> <age public key or 'passphrase'>` line per share. every share is written encrypted to <name>.age instead of showing it")
+            .long("encrypt-to")
+            .requires("encrypted-output")
+            .conflicts_with("shares-output")
+            .value_parser(clap::builder::PathBufValueParser::new()),
+        clap::Arg::new("encrypted-output")
+            .help("directory to write the encrypted shares to")
+            .long("encrypted-output")
+            .requires("encrypt-to")
+            .value_parser(clap::builder::PathBufValueParser::new()),
+        roster_arg().help("file with the names of the custodians receiving the new shares, one per line in share id order (defaults to the names of --encrypt-to)"),
+        clap::Arg::new("confirm-shares")
+            .help("after showing a share, let the custodian type it in again to check that it was written down correctly")
+            .long("confirm-shares")
+            .action(clap::ArgAction::SetTrue),
+    ]
+}
+
+pub fn parse() -> anyhow::Result<Args> {
+    let matches = build_args().get_matches();
+    let args = match matches.subcommand() {
+        Some(("rotate", matches)) => {
+            let split = parse_split_args(matches)?;
+            Args::Rotate(RotateArgs {
+                recover: parse_recover_args(
+                    matches,
+                    parse_old_shares_required(matches, &split),
+                    "old-roster",
+                )?,
+                output_file: parse_output_file(matches)?,
+                split,
+            })
+        }
+        Some(("reshare", matches)) => {
+            let split = parse_split_args(matches)?;
+            Args::Reshare(ReshareArgs {
+                recover: parse_recover_args(
+                    matches,
+                    parse_old_shares_required(matches, &split),
+                    "old-roster",
+                )?,
+                split,
+            })
+        }
+        Some(("verify", matches)) => Args::Verify(VerifyArgs {
+            recover: parse_recover_args(
+                matches,
+                *matches
+                    .get_one::<usize>("shares-required")
+                    .expect("required arg"),
+                "roster",
+            )?,
+        }),
+        Some(("create", matches)) => {
+            reject_global_args(matches, "create")?;
+            Args::Create(CreateArgs {
+                output_file: parse_output_file(matches)?,
+                split: parse_split_args(matches)?,
+            })
+        }
+        Some(("inspect", matches)) => {
+            reject_global_args(matches, "inspect")?;
+            Args::Inspect(InspectArgs {
+                dkek_file: parse_dkek_file(matches)?,
+            })
+        }
+        _ => unreachable!("subcommand is required"),
+    };
+    Ok(args)
+}
+
+fn reject_global_args(matches: &clap::ArgMatches, subcommand: &str) -> anyhow::Result<()> {
+    if let Some(arg) = GLOBAL_ARGS
+        .into_iter()
+        .find(|arg| matches.value_source(arg) == Some(clap::parser::ValueSource::CommandLine))
+    {
+        anyhow::bail!("--{arg} can't be used with {subcommand}, no shares are entered there!");
+    }
+    Ok(())
+}
+
+fn parse_dkek_file(matches: &clap::ArgMatches) -> anyhow::Result<PathBuf> {
+    let dkek_file = matches
+        .get_one::<PathBuf>("file")
+        .expect("required arg")
+        .clone();
+    if !dkek_file.exists() {
+        anyhow::bail!("specified dkek file does not exist!");
+    }
+    if !dkek_file.is_file() {
+        anyhow::bail!("specified dkek file is not a file!");
+    }
+    Ok(dkek_file)
+}
+
+fn parse_output_file(matches: &clap::ArgMatches) -> anyhow::Result<PathBuf> {
+    let output_file = matches
+        .get_one::<PathBuf>("output")
+        .expect("required arg")
+        .clone();
+    if output_file.exists() {
+        anyhow::bail!("specified output file already exists!");
+    }
+    Ok(output_file)
+}
+
+fn parse_recover_args(
+    matches: &clap::ArgMatches,
+    shares_required: usize,
+    roster_arg: &str,
+) -> anyhow::Result<RecoverArgs> {
+    let roster = matches
+        .get_one::<PathBuf>(roster_arg)
+        .map(|file| crate::roster::read_roster(file))
+        .transpose()?;
+    if roster
+        .as_ref()
+        .is_some_and(|roster| roster.len() < shares_required)
+    {
+        anyhow::bail!("the roster has fewer names than shares are required!");
+    }
+    Ok(RecoverArgs {
+        dkek_file: parse_dkek_file(matches)?,
+        shares_required,
+        typo_recovery: matches.get_flag("typo-recovery"),
+        shares_from: matches
+            .get_many::<PathBuf>("shares-from")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+        identities: matches
+            .get_many::<PathBuf>("identity")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+        roster,
+    })
+}
+
+fn parse_old_shares_required(matches: &clap::ArgMatches, split: &SplitArgs) -> usize {
+    matches
+        .get_one::<usize>("old-shares-required")
+        .copied()
+        .unwrap_or(split.shares_required)
+}
+
+fn parse_split_args(matches: &clap::ArgMatches) -> anyhow::Result<SplitArgs> {
+    let shares_total = *matches
+        .get_one::<usize>("shares-total")
+        .expect("required arg");
+    let shares_required = *matches
+        .get_one::<usize>("shares-required")
+        .expect("required arg");
+    if shares_required > shares_total {
+        anyhow::bail!(
+            "required number of shares must be less than or equal to total number of shares!"
+        );
+    }
+    let shares_output = matches.get_one::<PathBuf>("shares-output").cloned();
+    if shares_output
+        .as_ref()
+        .is_some_and(|f| f != Path::new("-") && f.exists())
+    {
+        anyhow::bail!("specified shares output file already exists!");
+    }
+    let share_encoding = match matches
+        .get_one::<String>("share-encoding")
+        .expect("default value")
+        .as_str()
+    {
+        "words" => ShareEncoding::Words,
+        _ => ShareEncoding::Hex,
+    };
+    let qr_output = matches.get_one::<PathBuf>("qr-output").cloned();
+    if let Some(dir) = &qr_output {
+        if !dir.is_dir() {
+            anyhow::bail!("specified qr output directory does not exist!");
+        }
+        // the share ids are 1 to shares_total
+        if let Some(file) = (1..=shares_total as u64)
+            .flat_map(|id| crate::output::qr_files(dir, id))
+            .find(|file| file.exists())
+        {
+            anyhow::bail!("qr code file {} already exists!", file.display());
+        }
+    }
+    let cards_output = matches.get_one::<PathBuf>("cards-output").cloned();
+    if let Some(dir) = &cards_output {
+        if !dir.is_dir() {
+            anyhow::bail!("specified cards output directory does not exist!");
+        }
+        if let Some(file) = (1..=shares_total as u64)
+            .map(|id| crate::cards::card_file(dir, id))
+            .find(|file| file.exists())
+        {
+            anyhow::bail!("share card {} already exists!", file.display());
+        }
+    }
+    let encrypt = parse_encrypt_args(matches, shares_total)?;
+    Ok(SplitArgs {
+        shares_total: NonZero::new(shares_total).expect("at least 2 shares"),
+        shares_required,
+        shares_output,
+        share_encoding,
+        qr: matches.get_flag("qr"),
+        qr_output,
+        cards_output,
+        roster: parse_roster(matches, shares_total, encrypt.as_ref())?,
+        confirm_shares: matches.get_flag("confirm-shares"),
+        encrypt,
+    })
+}
+
+fn parse_roster(
+    matches: &clap::ArgMatches,
+    shares_total: usize,
+    encrypt: Option<&EncryptArgs>,
+) -> anyhow::Result<Option<Roster>> {
+    let Some(file) = matches.get_one::<PathBuf>("roster") else {
+        return Ok(encrypt.map(|encrypt| {
+            encrypt
+                .custodians
+                .iter()
+                .map(|custodian| custodian.name.clone())
+                .collect()
+        }));
+    };
+    let roster = crate::roster::read_roster(file)?;
+    if roster.len() != shares_total {
+        anyhow::bail!(
+            "the roster has {} names, but {shares_total} shares are created!",
+            roster.len()
+        );
+    }
+    Ok(Some(roster))
+}
+
+fn parse_encrypt_args(
+    matches: &clap::ArgMatches,
+    shares_total: usize,
+) -> anyhow::Result<Option<EncryptArgs>> {
+    let Some(recipients) = matches.get_one::<PathBuf>("encrypt-to") else {
+        return Ok(None);
+    };
+    let output_dir = matches
+        .get_one::<PathBuf>("encrypted-output")
+        .expect("required by encrypt-to")
+        .clone();
+    if !output_dir.is_dir() {
+        anyhow::bail!("specified encrypted output directory does not exist!");
+    }
+    let custodians = crate::encrypt::read_recipients(recipients)
+        .map_err(|e| anyhow::anyhow!("failed to read recipients file: {e:#}"))?;
+    if custodians.len() != shares_total {
+        anyhow::bail!(
+            "the recipients file has {} recipients, but {shares_total} shares are created!",
+            custodians.len()
+        );
+    }
+    if let Some(file) = custodians
+        .iter()
+        .map(|custodian| crate::encrypt::share_file(&output_dir, custodian))
+        .find(|file| file.exists())
+    {
+        anyhow::bail!("encrypted share file {} already exists!", file.display());
+    }
+    Ok(Some(EncryptArgs {
+        output_dir,
+        custodians,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subcommand_matches(args: &[&str]) -> clap::ArgMatches {
+        let matches = build_args()
+            .try_get_matches_from(std::iter::once("sc-hsm-recrypt").chain(args.iter().copied()))
+            .unwrap();
+        matches.subcommand().unwrap().1.clone()
+    }
+
+    #[test]
+    fn global_args_before_and_after_subcommand() {
+        let verify = ["verify", "-f", "dkek.bin", "--shares-required", "2"];
+        for matches in [
+            subcommand_matches(
+                &[&["--shares-from", "a.json", "--typo-recovery"][..], &verify].concat(),
+            ),
+            subcommand_matches(
+                &[&verify[..], &["--shares-from", "a.json", "--typo-recovery"]].concat(),
+            ),
+        ] {
+            let shares_from = matches
+                .get_many::<PathBuf>("shares-from")
+                .unwrap()
+                .collect::<Vec<_>>();
+            assert_eq!(shares_from, [Path::new("a.json")]);
+            assert!(matches.get_flag("typo-recovery"));
+            assert!(reject_global_args(&matches, "verify").is_err());
+        }
+    }
+
+    #[test]
+    fn global_args_rejected_without_shares() {
+        let matches = subcommand_matches(&["inspect", "-f", "dkek.bin"]);
+        assert!(reject_global_args(&matches, "inspect").is_ok());
+        let matches = subcommand_matches(&["--identity", "key.txt", "inspect", "-f", "dkek.bin"]);
+        let error = reject_global_args(&matches, "inspect").unwrap_err();
+        assert!(error.to_string().contains("--identity"), "{error}");
+    }
+
+    // clap exits by itself, with code 2 of the table in the readme
+    #[test]
+    fn invalid_arguments_exit_code() {
+        let e = build_args()
+            .try_get_matches_from(["sc-hsm-recrypt", "verify", "--no-such-option"])
+            .unwrap_err();
+        assert_eq!(e.exit_code(), 2);
+    }
+}
@@ -0,0 +1,97 @@
+//! printable share cards, one html file per custodian.
+//!
+//! the cards don't reference anything external (the qr code is an inline svg), so they can be printed on an
+//! offline machine. the page size is a6, which fits into c6 envelopes.
+
+use std::path::{Path, PathBuf};
+
+use crate::{args::SplitArgs, dkek::Dkek, U64Share};
+
+pub fn card_file(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("card-{id}.html"))
+}
+
+// writes card-<id>.html for every share
+pub fn write_cards(
+    dir: &Path,
+    modulus: &crypto_bigint::U64,
+    shares: &[U64Share],
+    split: &SplitArgs,
+    dkek: &Dkek,
+) -> anyhow::Result<()> {
+    let date = ceremony_date();
+    let kcv = crate::ui::format_bytes(&crate::dkek::kcv(dkek));
+    for share in shares {
+        let id = share.identifier.retrieve()?.as_words()[0];
+        let value = share.value.retrieve()?;
+        let qr = crate::output::share_qr_code(modulus, share, split)?
+            .render::<qrcode::render::svg::Color>()
+            .min_dimensions(200, 200)
+            .build();
+        // the svg is embedded into the html, so it must not have an xml declaration
+        let qr = qr.split_once("?>").map_or(qr.as_str(), |(_, svg)| svg);
+
+        let card = format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>dkek share {id}</title>
+<style>
+@page {{ size: A6; margin: 8mm; }}
+body {{ font-family: sans-serif; font-size: 9pt; margin: 0; }}
+h1 {{ font-size: 12pt; margin: 0 0 4mm 0; }}
+table {{ border-collapse: collapse; width: 100%; }}
+th {{ text-align: left; padding-right: 3mm; white-space: nowrap; vertical-align: top; }}
+td {{ font-family: monospace; font-size: 10pt; word-break: break-word; }}
+.qr {{ text-align: center; margin-top: 4mm; }}
+.qr svg {{ width: 45mm; height: 45mm; }}
+</style>
+</head>
+<body>
+<h1>dkek share {id} of {total}</h1>
+<table>
+{custodian}<tr><th>policy</th><td>{required} of {total} shares required</td></tr>
+<tr><th>date</th><td>{date}</td></tr>
+<tr><th>prime</th><td>{prime}</td></tr>
+<tr><th>share id</th><td>{id}</td></tr>
+<tr><th>share value</th><td>{value}</td></tr>
+<tr><th>checksum</th><td>{checksum}</td></tr>
+<tr><th>dkek kcv</th><td>{kcv}</td></tr>
+</table>
+<div class="qr">{qr}</div>
+</body>
+</html>
+"#,
+            total = split.shares_total,
+            required = split.shares_required,
+            prime = crate::ui::format_value(modulus, split.share_encoding),
+            value = crate::ui::format_value(&value, split.share_encoding),
+            checksum = crate::checksum::format_checksum(modulus, id, &value),
+            custodian = crate::roster::name(split.roster.as_deref(), id)
+                .map(|name| format!(
+                    "<tr><th>custodian</th><td>{}</td></tr>\n",
+                    escape_html(name)
+                ))
+                .unwrap_or_default(),
+        );
+
+        crate::output::write_new_file(&card_file(dir, id), card.as_bytes())?;
+    }
+    Ok(())
+}
+
+// the names in the roster are the only text on the card that is not generated by us
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn ceremony_date() -> time::Date {
+    // the local offset can't always be determined, the utc date is close enough then
+    time::OffsetDateTime::now_local()
+        .unwrap_or_else(|_| time::OffsetDateTime::now_utc())
+        .date()
+}
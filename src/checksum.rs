@@ -0,0 +1,98 @@
+//! short checksums shown next to every new share, so typos are noticed right when a share is entered
+//! instead of only after the whole kdf ran. shares of `sc-hsm-tool` don't have one, so it is always optional
+
+use crypto_bigint::Encoding;
+
+use crate::ui::InputValidationError;
+
+pub type Checksum = [u8; 2];
+
+// the first bytes of sha256(prime || share id || share value), all big endian
+pub fn checksum(prime: &crypto_bigint::U64, id: u64, value: &crypto_bigint::U64) -> Checksum {
+    use sha2::Digest;
+    let hash = sha2::Sha256::new()
+        .chain_update(prime.to_be_bytes())
+        .chain_update(id.to_be_bytes())
+        .chain_update(value.to_be_bytes())
+        .finalize();
+    let mut checksum = Checksum::default();
+    checksum.copy_from_slice(&hash[..std::mem::size_of::<Checksum>()]);
+    checksum
+}
+
+pub fn format_checksum(prime: &crypto_bigint::U64, id: u64, value: &crypto_bigint::U64) -> String {
+    crate::ui::format_bytes(&checksum(prime, id, value))
+}
+
+// check an entered checksum (with or without colons) against the share it was printed with
+pub fn verify(
+    prime: &crypto_bigint::U64,
+    id: u64,
+    value: &crypto_bigint::U64,
+    input: &str,
+) -> Result<(), InputValidationError> {
+    let input = input.trim().replace(':', "");
+    if input.chars().any(|c| !c.is_ascii_hexdigit()) {
+        return Err(InputValidationError::InputNotHex);
+    }
+    if input.len() != 2 * std::mem::size_of::<Checksum>() {
+        return Err(InputValidationError::WrongLength);
+    }
+    if !input.eq_ignore_ascii_case(&format_checksum(prime, id, value).replace(':', "")) {
+        return Err(InputValidationError::ChecksumMismatch);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u64(value: u64) -> crypto_bigint::U64 {
+        crypto_bigint::U64::from_u64(value)
+    }
+
+    // share id 1 of `tests/data/shares.json`
+    const PRIME: u64 = 0xbc76_8f0f_e233_6529;
+    const VALUE: u64 = 0x6290_69da_9078_72e0;
+
+    #[test]
+    fn format() {
+        assert_eq!(format_checksum(&u64(PRIME), 1, &u64(VALUE)), "59:2d");
+    }
+
+    #[test]
+    fn verify_with_and_without_colons() {
+        for input in ["59:2d", "592d", " 59:2D ", "592D"] {
+            assert!(
+                verify(&u64(PRIME), 1, &u64(VALUE), input).is_ok(),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn verify_wrong_share() {
+        // a typo in the value and a wrong share id both change the checksum
+        assert!(matches!(
+            verify(&u64(PRIME), 1, &u64(VALUE ^ 1), "59:2d"),
+            Err(InputValidationError::ChecksumMismatch)
+        ));
+        assert!(matches!(
+            verify(&u64(PRIME), 2, &u64(VALUE), "59:2d"),
+            Err(InputValidationError::ChecksumMismatch)
+        ));
+    }
+
+    #[test]
+    fn verify_invalid_input() {
+        assert!(matches!(
+            verify(&u64(PRIME), 1, &u64(VALUE), "59:2"),
+            Err(InputValidationError::WrongLength)
+        ));
+        assert!(matches!(
+            verify(&u64(PRIME), 1, &u64(VALUE), "59:2g"),
+            Err(InputValidationError::InputNotHex)
+        ));
+    }
+}
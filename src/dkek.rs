@@ -0,0 +1,323 @@
+//! reading and writing dkek share files in the format used by `sc-hsm-tool`
+//!
+//! a dkek share file is always 64 bytes long: the openssl `Salted__` magic, an 8 byte salt and the 32 byte dkek
+//! encrypted with aes-256-cbc (pkcs7 padded, so 48 bytes of ciphertext). key and iv are derived from the password
+//! with openssl's `EVP_BytesToKey` using md5 and a ridiculous amount of iterations.
+
+use std::{
+    io::Read,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+
+type Decryptor = cbc::Decryptor<aes::Aes256>;
+type Encryptor = cbc::Encryptor<aes::Aes256>;
+type EncryptionKey = [u8; 32];
+type EncryptionIv = [u8; 16];
+
+pub type Dkek = [u8; 32];
+pub type Password = [u8; 8];
+pub type Salt = [u8; 8];
+pub type DkekFile = [u8; DKEK_FILE_LEN];
+pub type Kcv = [u8; 8];
+
+pub const DKEK_FILE_LEN: usize = 64;
+
+// these values are just taken from the sc-hsm-tool source code
+const KDF_ITERATIONS: usize = 10_000_000;
+const MAGIC: &str = "Salted__";
+// how many md5 iterations are done before progress is reported and cancellation is checked
+const KDF_PROGRESS_STEP: usize = 100_000;
+
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+// shared between the threads running the kdf and the ui showing their progress
+#[derive(Default)]
+pub struct KdfProgress {
+    message: Mutex<String>,
+    kdf_runs: AtomicUsize,
+    iterations: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl KdfProgress {
+    // start a new phase which is expected to run the kdf `kdf_runs` times
+    pub fn start(&self, message: impl Into<String>, kdf_runs: usize) {
+        *self.message.lock().unwrap() = message.into();
+        self.kdf_runs.store(kdf_runs, Ordering::Relaxed);
+        self.iterations.store(0, Ordering::Relaxed);
+    }
+
+    pub fn message(&self) -> String {
+        self.message.lock().unwrap().clone()
+    }
+
+    // between 0 and 1
+    pub fn fraction(&self) -> f64 {
+        // each kdf run consists of three hash chains
+        let total = self.kdf_runs.load(Ordering::Relaxed) * 3 * KDF_ITERATIONS;
+        if total == 0 {
+            return 0.0;
+        }
+        (self.iterations.load(Ordering::Relaxed) as f64 / total as f64).min(1.0)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.is_cancelled() {
+            return Err(Cancelled);
+        }
+        Ok(())
+    }
+}
+
+pub fn read_dkek_file<P: AsRef<Path>>(file: P) -> anyhow::Result<DkekFile> {
+    let mut file = std::fs::File::open(file)?;
+    let mut bytes = [0; DKEK_FILE_LEN];
+    file.read_exact(&mut bytes)?;
+    if file.read(&mut [0])? != 0 {
+        anyhow::bail!("dkek file is longer than {DKEK_FILE_LEN} bytes!");
+    }
+    Ok(bytes)
+}
+
+pub fn write_dkek_file<P: AsRef<Path>>(file: P, bytes: &DkekFile) -> anyhow::Result<()> {
+    // an existing dkek share file might be the only backup
+    crate::output::write_new_file(file.as_ref(), bytes)
+}
+
+pub fn salt(bytes: &DkekFile) -> anyhow::Result<Salt> {
+    if &bytes[0..8] != MAGIC.as_bytes() {
+        anyhow::bail!("dkek file doesn't start with the correct header!");
+    }
+    let mut salt = Salt::default();
+    salt.copy_from_slice(&bytes[8..16]);
+    Ok(salt)
+}
+
+pub fn decrypt_dkek(
+    bytes: &DkekFile,
+    password: &Password,
+    progress: &KdfProgress,
+) -> anyhow::Result<Dkek> {
+    let salt = salt(bytes)?;
+    let mut data = [0; 48];
+    data.copy_from_slice(&bytes[16..]);
+
+    let (key, iv) = derive_key_iv(&salt, password, progress)?;
+    let dec = Decryptor::new(&key.into(), &iv.into())
+        .decrypt_padded_mut::<cbc::cipher::block_padding::Pkcs7>(&mut data)?;
+    // with a wrong key the padding is valid by accident every now and then, but it's really unlikely to be a full block
+    if dec.len() != std::mem::size_of::<Dkek>() {
+        anyhow::bail!("decrypted dkek has the wrong length!");
+    }
+    let mut data = Dkek::default();
+    data.copy_from_slice(dec);
+
+    Ok(data)
+}
+
+pub fn encrypt_dkek(
+    dkek: &Dkek,
+    password: &Password,
+    salt: &Salt,
+    progress: &KdfProgress,
+) -> Result<DkekFile, Cancelled> {
+    let (key, iv) = derive_key_iv(salt, password, progress)?;
+    let mut data = [0_u8; 48];
+    Encryptor::new(&key.into(), &iv.into())
+        .encrypt_padded_b2b_mut::<cbc::cipher::block_padding::Pkcs7>(dkek, &mut data)
+        .expect("output buffer fits a padded dkek");
+
+    let mut bytes = [0_u8; DKEK_FILE_LEN];
+    bytes[0..8].copy_from_slice(MAGIC.as_bytes());
+    bytes[8..16].copy_from_slice(salt);
+    bytes[16..].copy_from_slice(&data);
+    Ok(bytes)
+}
+
+// the key check value is what the smart card hsm (and sc-hsm-tool) shows to identify a dkek
+pub fn kcv(dkek: &Dkek) -> Kcv {
+    use sha2::Digest;
+    let hash = sha2::Sha256::digest(dkek);
+    let mut kcv = Kcv::default();
+    kcv.copy_from_slice(&hash[..8]);
+    kcv
+}
+
+// this is an impl of openssl's EVP_BytesToKey (according to the docs at least)
+// but specifically for aes_256_cbc/md5/10_000_000 iterations
+fn derive_key_iv(
+    salt: &Salt,
+    secret: &Password,
+    progress: &KdfProgress,
+) -> Result<(EncryptionKey, EncryptionIv), Cancelled> {
+    fn hash(
+        previous: &[u8],
+        salt: &Salt,
+        secret: &Password,
+        progress: &KdfProgress,
+    ) -> Result<[u8; 16], Cancelled> {
+        debug_assert!(previous.len() <= 16);
+        let mut full_data = [0_u8; 32];
+        full_data[0..previous.len()].copy_from_slice(previous);
+        full_data[previous.len()..previous.len() + 8].copy_from_slice(secret);
+        full_data[previous.len() + 8..previous.len() + 16].copy_from_slice(salt);
+
+        let mut hash = *md5::compute(&full_data[..previous.len() + 16]);
+        for i in 1..KDF_ITERATIONS {
+            if i % KDF_PROGRESS_STEP == 0 {
+                progress.check()?;
+                progress
+                    .iterations
+                    .fetch_add(KDF_PROGRESS_STEP, Ordering::Relaxed);
+            }
+            hash = *md5::compute(hash);
+        }
+        progress
+            .iterations
+            .fetch_add(KDF_PROGRESS_STEP, Ordering::Relaxed);
+        Ok(hash)
+    }
+
+    let d1 = hash(&[], salt, secret, progress)?;
+    let d2 = hash(&d1, salt, secret, progress)?;
+    let d3 = hash(&d2, salt, secret, progress)?;
+
+    let mut key = [0_u8; 32];
+    key[0..16].copy_from_slice(&d1);
+    key[16..32].copy_from_slice(&d2);
+    Ok((key, d3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // made with openssl's `EVP_BytesToKey(EVP_aes_256_cbc(), EVP_md5(), salt, password, 8, 10000000, ...)` and
+    // aes-256-cbc, which are the calls sc-hsm-tool uses to write dkek share files
+    const OPENSSL_FILE: &str = "53616c7465645f5f10111213141516179360c5f5447178aa7dcb5906608fdbed\
+                                6b3e659c79e33df3662e2fa1f8542c9834a18d0e8f6fd7df63ec599da671b2a2";
+    const OPENSSL_SALT: Salt = [0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17];
+    const OPENSSL_PASSWORD: Password = [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef];
+
+    fn openssl_dkek() -> Dkek {
+        std::array::from_fn(|i| i as u8)
+    }
+
+    fn openssl_file() -> DkekFile {
+        std::array::from_fn(|i| {
+            u8::from_str_radix(&OPENSSL_FILE[i * 2..i * 2 + 2], 16).expect("valid hex")
+        })
+    }
+
+    #[test]
+    fn decrypts_openssl_file() {
+        let dkek = decrypt_dkek(&openssl_file(), &OPENSSL_PASSWORD, &KdfProgress::default());
+        assert_eq!(dkek.unwrap(), openssl_dkek());
+    }
+
+    #[test]
+    fn encrypts_like_openssl() {
+        let file = encrypt_dkek(
+            &openssl_dkek(),
+            &OPENSSL_PASSWORD,
+            &OPENSSL_SALT,
+            &KdfProgress::default(),
+        );
+        assert_eq!(file.unwrap(), openssl_file());
+    }
+
+    #[test]
+    fn round_trip() {
+        let dkek = [0xa5; 32];
+        let password = *b"password";
+        let progress = KdfProgress::default();
+        let file = encrypt_dkek(&dkek, &password, b"saltsalt", &progress).unwrap();
+        assert_eq!(salt(&file).unwrap(), *b"saltsalt");
+        assert_eq!(decrypt_dkek(&file, &password, &progress).unwrap(), dkek);
+    }
+
+    #[test]
+    fn rejects_wrong_password() {
+        let mut password = OPENSSL_PASSWORD;
+        password[7] ^= 1;
+        assert!(decrypt_dkek(&openssl_file(), &password, &KdfProgress::default()).is_err());
+    }
+
+    #[test]
+    fn cancelled_kdf() {
+        let progress = KdfProgress::default();
+        progress.cancel();
+        assert!(
+            encrypt_dkek(&openssl_dkek(), &OPENSSL_PASSWORD, &OPENSSL_SALT, &progress).is_err()
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_header() {
+        let mut file = openssl_file();
+        assert_eq!(salt(&file).unwrap(), OPENSSL_SALT);
+        file[0] = b's';
+        assert!(salt(&file).is_err());
+        assert!(decrypt_dkek(&file, &OPENSSL_PASSWORD, &KdfProgress::default()).is_err());
+    }
+
+    #[test]
+    fn reads_only_files_of_the_right_length() {
+        let dir = std::env::temp_dir().join(format!("sc-hsm-recrypt-dkek-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = openssl_file();
+        let read = |name: &str, bytes: &[u8]| {
+            let path = dir.join(name);
+            std::fs::write(&path, bytes).unwrap();
+            read_dkek_file(&path)
+        };
+
+        let exact = read("exact", &file);
+        let short = read("short", &file[..DKEK_FILE_LEN - 1]);
+        let long = read("long", &[&file[..], &[0]].concat());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(exact.unwrap(), file);
+        assert!(short.is_err());
+        assert!(long.is_err());
+    }
+
+    #[test]
+    fn writes_no_existing_file() {
+        let dir = std::env::temp_dir().join(format!("sc-hsm-recrypt-write-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("dkek.bin");
+
+        let first = write_dkek_file(&path, &openssl_file());
+        let second = write_dkek_file(&path, &[0; DKEK_FILE_LEN]);
+        let written = read_dkek_file(&path);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(first.is_ok());
+        assert!(second.is_err());
+        assert_eq!(written.unwrap(), openssl_file());
+    }
+}
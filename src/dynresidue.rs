@@ -3,6 +3,10 @@
 //! this entire implementation may be extremely terrible because for some things (like [`WrappedDynResidue::ZERO`]) it
 //! just can't know the correct modulus so moduli are copied on operations.
 //! do not even try to use this with different moduli, things *will* absolutely be fucked up. but it works.
+//!
+//! the arithmetic operators can't fail, so an overflow of plain integers results in [`WrappedDynResidue::Overflow`],
+//! which sticks to every result computed from it. [`WrappedDynResidue::retrieve`] fails for it, so it can't be
+//! mistaken for a value.
 
 use std::ops::{Add, AddAssign, Deref, DerefMut, Mul, MulAssign, Sub, SubAssign};
 
@@ -16,6 +20,7 @@ use vsss_rs::{ShareElement, ShareIdentifier, VsssResult};
 pub enum WrappedDynResidue<const LIMBS: usize> {
     Residue(DynResidue<LIMBS>),
     Integer(Uint<LIMBS>),
+    Overflow,
 }
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct IdentifierDynResidue<const LIMBS: usize>(pub WrappedDynResidue<LIMBS>);
@@ -26,45 +31,48 @@ impl<const LIMBS: usize> WrappedDynResidue<LIMBS> {
 
     pub fn sub(&self, rhs: &Self) -> Self {
         match (self, rhs) {
+            (Self::Overflow, _) | (_, Self::Overflow) => Self::Overflow,
             (Self::Residue(res0), Self::Residue(res1)) => Self::Residue(res0 - res1),
             (Self::Residue(res), Self::Integer(int)) => {
                 Self::Residue(res - DynResidue::new(int, *res.params()))
             }
             (Self::Integer(int), Self::Residue(res)) => {
-                Self::Residue(DynResidue::new(&int, *res.params()) - res)
+                Self::Residue(DynResidue::new(int, *res.params()) - res)
             }
             (Self::Integer(int0), Self::Integer(int1)) => {
-                Self::Integer(int0.checked_sub(int1).unwrap())
+                Option::from(int0.checked_sub(int1)).map_or(Self::Overflow, Self::Integer)
             }
         }
     }
 
     pub fn add(&self, rhs: &Self) -> Self {
         match (self, rhs) {
+            (Self::Overflow, _) | (_, Self::Overflow) => Self::Overflow,
             (Self::Residue(res0), Self::Residue(res1)) => Self::Residue(res0 + res1),
             (Self::Residue(res), Self::Integer(int)) => {
                 Self::Residue(res + DynResidue::new(int, *res.params()))
             }
             (Self::Integer(int), Self::Residue(res)) => {
-                Self::Residue(DynResidue::new(&int, *res.params()) + res)
+                Self::Residue(DynResidue::new(int, *res.params()) + res)
             }
             (Self::Integer(int0), Self::Integer(int1)) => {
-                Self::Integer(int0.checked_add(int1).unwrap())
+                Option::from(int0.checked_add(int1)).map_or(Self::Overflow, Self::Integer)
             }
         }
     }
 
     pub fn mul(&self, rhs: &Self) -> Self {
         match (self, rhs) {
+            (Self::Overflow, _) | (_, Self::Overflow) => Self::Overflow,
             (Self::Residue(res0), Self::Residue(res1)) => Self::Residue(res0 * res1),
             (Self::Residue(res), Self::Integer(int)) => {
                 Self::Residue(res * DynResidue::new(int, *res.params()))
             }
             (Self::Integer(int), Self::Residue(res)) => {
-                Self::Residue(DynResidue::new(&int, *res.params()) * res)
+                Self::Residue(DynResidue::new(int, *res.params()) * res)
             }
             (Self::Integer(int0), Self::Integer(int1)) => {
-                Self::Integer(int0.checked_mul(int1).unwrap())
+                Option::from(int0.checked_mul(int1)).map_or(Self::Overflow, Self::Integer)
             }
         }
     }
@@ -74,6 +82,7 @@ impl<const LIMBS: usize> WrappedDynResidue<LIMBS> {
             Self::Integer(int) => int.is_zero(),
             // this does not need retrieval because the montgomery form of zero is zero
             Self::Residue(res) => res.as_montgomery().is_zero(),
+            Self::Overflow => vsss_rs::subtle::Choice::from(0),
         }
     }
 
@@ -83,14 +92,15 @@ impl<const LIMBS: usize> WrappedDynResidue<LIMBS> {
                 let val = Invert::invert(res);
                 val.into_option().map(Self::Residue)
             }
-            Self::Integer(_) => None,
+            Self::Integer(_) | Self::Overflow => None,
         }
     }
 
-    pub fn retrieve(&self) -> Uint<LIMBS> {
+    pub fn retrieve(&self) -> Result<Uint<LIMBS>, crate::error::Error> {
         match self {
-            Self::Residue(res) => res.retrieve(),
-            Self::Integer(int) => *int,
+            Self::Residue(res) => Ok(res.retrieve()),
+            Self::Integer(int) => Ok(*int),
+            Self::Overflow => Err(crate::error::Error::Arithmetic),
         }
     }
 }
@@ -126,13 +136,13 @@ impl<const LIMBS: usize> Sub<&Self> for WrappedDynResidue<LIMBS> {
 
 impl<const LIMBS: usize> SubAssign for WrappedDynResidue<LIMBS> {
     fn sub_assign(&mut self, rhs: Self) {
-        *self = Self::sub(&self, &rhs);
+        *self = Self::sub(self, &rhs);
     }
 }
 
 impl<const LIMBS: usize> SubAssign<&Self> for WrappedDynResidue<LIMBS> {
     fn sub_assign(&mut self, rhs: &Self) {
-        *self = Self::sub(&self, rhs);
+        *self = Self::sub(self, rhs);
     }
 }
 
@@ -152,13 +162,13 @@ impl<const LIMBS: usize> Add<&Self> for WrappedDynResidue<LIMBS> {
 
 impl<const LIMBS: usize> AddAssign for WrappedDynResidue<LIMBS> {
     fn add_assign(&mut self, rhs: Self) {
-        *self = Self::add(&self, &rhs);
+        *self = Self::add(self, &rhs);
     }
 }
 
 impl<const LIMBS: usize> AddAssign<&Self> for WrappedDynResidue<LIMBS> {
     fn add_assign(&mut self, rhs: &Self) {
-        *self = Self::add(&self, rhs);
+        *self = Self::add(self, rhs);
     }
 }
 
@@ -178,13 +188,13 @@ impl<const LIMBS: usize> Mul<&Self> for WrappedDynResidue<LIMBS> {
 
 impl<const LIMBS: usize> MulAssign for WrappedDynResidue<LIMBS> {
     fn mul_assign(&mut self, rhs: Self) {
-        *self = Self::mul(&self, &rhs)
+        *self = Self::mul(self, &rhs)
     }
 }
 
 impl<const LIMBS: usize> MulAssign<&Self> for WrappedDynResidue<LIMBS> {
     fn mul_assign(&mut self, rhs: &Self) {
-        *self = Self::mul(&self, rhs)
+        *self = Self::mul(self, rhs)
     }
 }
 
@@ -259,7 +269,8 @@ where
     }
 
     fn serialize(&self) -> Self::Serialization {
-        self.retrieve().to_be_bytes()
+        // can't fail here, an overflow serializes like zero, which is never a valid share identifier
+        self.retrieve().unwrap_or_default().to_be_bytes()
     }
 
     fn deserialize(_serialized: &Self::Serialization) -> VsssResult<Self> {
@@ -294,3 +305,67 @@ where
             .map_or(Err(vsss_rs::Error::NotImplemented), |v| Ok(Self(v)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Wrapped = WrappedDynResidue<{ crypto_bigint::U64::LIMBS }>;
+
+    fn residue(value: u64) -> Wrapped {
+        let params = DynResidueParams::new(&crypto_bigint::U64::from_u64(0xbc76_8f0f_e233_6529));
+        Wrapped::Residue(DynResidue::new(
+            &crypto_bigint::U64::from_u64(value),
+            params,
+        ))
+    }
+
+    fn integer(value: u64) -> Wrapped {
+        Wrapped::Integer(crypto_bigint::U64::from_u64(value))
+    }
+
+    #[test]
+    fn integers_overflow() {
+        assert_eq!(integer(u64::MAX) + integer(1), Wrapped::Overflow);
+        assert_eq!(integer(0) - integer(1), Wrapped::Overflow);
+        assert_eq!(integer(u64::MAX) * integer(2), Wrapped::Overflow);
+        assert_eq!(integer(2) * integer(3), integer(6));
+    }
+
+    #[test]
+    fn overflow_sticks() {
+        for value in [integer(5), residue(5), Wrapped::Overflow] {
+            assert_eq!(Wrapped::Overflow + value, Wrapped::Overflow);
+            assert_eq!(value + Wrapped::Overflow, Wrapped::Overflow);
+            assert_eq!(Wrapped::Overflow - value, Wrapped::Overflow);
+            assert_eq!(value - Wrapped::Overflow, Wrapped::Overflow);
+            assert_eq!(Wrapped::Overflow * value, Wrapped::Overflow);
+            assert_eq!(value * Wrapped::Overflow, Wrapped::Overflow);
+        }
+        // residues don't overflow, but an overflow before them isn't lost
+        assert_eq!(
+            (integer(0) - integer(1)) * residue(3) + residue(4),
+            Wrapped::Overflow
+        );
+    }
+
+    #[test]
+    fn overflow_is_no_value() {
+        assert!(matches!(
+            Wrapped::Overflow.retrieve(),
+            Err(crate::error::Error::Arithmetic)
+        ));
+        assert!(matches!(
+            (integer(u64::MAX) + integer(1)).retrieve(),
+            Err(crate::error::Error::Arithmetic)
+        ));
+        assert_eq!(
+            residue(7).retrieve().unwrap(),
+            crypto_bigint::U64::from_u64(7)
+        );
+        assert_eq!(
+            integer(7).retrieve().unwrap(),
+            crypto_bigint::U64::from_u64(7)
+        );
+    }
+}
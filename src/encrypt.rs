@@ -0,0 +1,198 @@
+//! per-custodian share files, encrypted with age to the custodian's x25519 public key or passphrase.
+//!
+//! the recipients file has one `<name> <recipient>` line per share (in share id order), where the recipient is
+//! either an `age1...` public key or `passphrase` to ask the custodian for a passphrase during the ceremony.
+//! empty lines and lines starting with `#` are ignored. every share is written to `<name>.age`.
+
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use age::secrecy::SecretString;
+
+pub struct EncryptArgs {
+    pub output_dir: PathBuf,
+    pub custodians: Vec<Custodian>,
+}
+
+pub struct Custodian {
+    pub name: String,
+    pub recipient: Recipient,
+}
+
+pub enum Recipient {
+    X25519(age::x25519::Recipient),
+    Passphrase,
+}
+
+pub fn read_recipients(file: &Path) -> anyhow::Result<Vec<Custodian>> {
+    let content = std::fs::read_to_string(file)?;
+    let custodians = content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_number, line)| {
+            let Some((name, recipient)) = line.split_once(char::is_whitespace) else {
+                anyhow::bail!("line {line_number}: expected a name and a recipient");
+            };
+            // the name becomes the file name
+            if name.starts_with('.')
+                || !name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            {
+                anyhow::bail!(
+                    "line {line_number}: names may only contain letters, digits, `-`, `_` and `.`"
+                );
+            }
+            let recipient =
+                match recipient.trim() {
+                    "passphrase" => Recipient::Passphrase,
+                    recipient => Recipient::X25519(recipient.parse().map_err(|e| {
+                        anyhow::anyhow!("line {line_number}: invalid recipient: {e}")
+                    })?),
+                };
+            Ok(Custodian {
+                name: name.to_owned(),
+                recipient,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    for (i, custodian) in custodians.iter().enumerate() {
+        if custodians[..i]
+            .iter()
+            .any(|other| other.name == custodian.name)
+        {
+            anyhow::bail!("name {} is used more than once", custodian.name);
+        }
+    }
+    Ok(custodians)
+}
+
+pub fn share_file(output_dir: &Path, custodian: &Custodian) -> PathBuf {
+    output_dir.join(format!("{}.age", custodian.name))
+}
+
+// encrypt to a single recipient, ascii armored so the file can be stored in a password manager as well
+pub fn encrypt(plaintext: &[u8], recipient: &dyn age::Recipient) -> anyhow::Result<Vec<u8>> {
+    let encryptor = age::Encryptor::with_recipients(std::iter::once(recipient))?;
+    let armor = age::armor::ArmoredWriter::wrap_output(Vec::new(), age::armor::Format::AsciiArmor)?;
+    let mut writer = encryptor.wrap_output(armor)?;
+    writer.write_all(plaintext)?;
+    Ok(writer.finish()?.finish()?)
+}
+
+pub fn is_encrypted(content: &[u8]) -> bool {
+    content.starts_with(b"age-encryption.org/")
+        || content
+            .trim_ascii_start()
+            .starts_with(b"-----BEGIN AGE ENCRYPTED FILE-----")
+}
+
+pub fn read_identities(files: &[PathBuf]) -> anyhow::Result<Vec<Box<dyn age::Identity>>> {
+    let mut identities = Vec::new();
+    for file in files {
+        let identity_file = age::IdentityFile::from_file(file.to_string_lossy().into_owned())
+            .map_err(|e| anyhow::anyhow!("failed to read identity file {}: {e}", file.display()))?;
+        identities.extend(identity_file.into_identities()?);
+    }
+    Ok(identities)
+}
+
+// passphrase encrypted files can't be decrypted with an identity, `get_passphrase` is only asked for these
+pub fn decrypt(
+    content: &[u8],
+    identities: &[Box<dyn age::Identity>],
+    get_passphrase: impl FnOnce() -> anyhow::Result<SecretString>,
+) -> anyhow::Result<Vec<u8>> {
+    let decryptor = age::Decryptor::new(age::armor::ArmoredReader::new(content))?;
+    let mut reader = if decryptor.is_scrypt() {
+        let identity = age::scrypt::Identity::new(get_passphrase()?);
+        decryptor.decrypt(std::iter::once(&identity as &dyn age::Identity))?
+    } else {
+        if identities.is_empty() {
+            anyhow::bail!(
+                "the file is encrypted to a public key, pass the matching identity with --identity"
+            );
+        }
+        decryptor.decrypt(identities.iter().map(|identity| identity.as_ref()))?
+    };
+    let mut plaintext = Vec::new();
+    reader.read_to_end(&mut plaintext)?;
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHARE: &[u8] = b"{\"prime\":\"bc:76:8f:0f:e2:33:65:29\"}";
+
+    fn no_passphrase() -> anyhow::Result<SecretString> {
+        panic!("asked for a passphrase")
+    }
+
+    #[test]
+    fn x25519_round_trip() {
+        let identity = age::x25519::Identity::generate();
+        let encrypted = encrypt(SHARE, &identity.to_public()).unwrap();
+        assert!(is_encrypted(&encrypted));
+        let identities: Vec<Box<dyn age::Identity>> = vec![Box::new(identity)];
+        assert_eq!(
+            decrypt(&encrypted, &identities, no_passphrase).unwrap(),
+            SHARE
+        );
+    }
+
+    #[test]
+    fn x25519_wrong_identity() {
+        let encrypted = encrypt(SHARE, &age::x25519::Identity::generate().to_public()).unwrap();
+        let identities: Vec<Box<dyn age::Identity>> =
+            vec![Box::new(age::x25519::Identity::generate())];
+        assert!(decrypt(&encrypted, &identities, no_passphrase).is_err());
+    }
+
+    #[test]
+    fn passphrase_round_trip() {
+        let mut recipient = age::scrypt::Recipient::new(SecretString::from("correct horse"));
+        // the default work factor takes seconds
+        recipient.set_work_factor(10);
+        let encrypted = encrypt(SHARE, &recipient).unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(
+            decrypt(&encrypted, &[], || Ok(SecretString::from("correct horse"))).unwrap(),
+            SHARE
+        );
+        assert!(decrypt(&encrypted, &[], || Ok(SecretString::from("battery staple"))).is_err());
+    }
+
+    #[test]
+    fn encrypted_headers() {
+        let recipient = age::x25519::Identity::generate().to_public();
+        let armored = encrypt(SHARE, &recipient).unwrap();
+        assert!(armored.starts_with(b"-----BEGIN AGE ENCRYPTED FILE-----"));
+        assert!(is_encrypted(&armored));
+        // armored files pasted with some leading whitespace
+        assert!(is_encrypted(&[b"\n  ".as_slice(), &armored].concat()));
+
+        let mut binary = Vec::new();
+        let encryptor = age::Encryptor::with_recipients(std::iter::once(&recipient as _)).unwrap();
+        let mut writer = encryptor.wrap_output(&mut binary).unwrap();
+        writer.write_all(SHARE).unwrap();
+        writer.finish().unwrap();
+        assert!(binary.starts_with(b"age-encryption.org/v1\n"));
+        assert!(is_encrypted(&binary));
+
+        assert!(!is_encrypted(SHARE));
+        assert!(!is_encrypted(b"Salted__"));
+    }
+
+    #[test]
+    fn x25519_without_identity() {
+        let encrypted = encrypt(SHARE, &age::x25519::Identity::generate().to_public()).unwrap();
+        let err = decrypt(&encrypted, &[], no_passphrase).unwrap_err();
+        assert!(err.to_string().contains("--identity"), "{err}");
+    }
+}
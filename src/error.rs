@@ -0,0 +1,26 @@
+//! errors of the share arithmetic, which get their own exit codes so wrapper scripts can tell them apart.
+//!
+//! everything else (wrong input, i/o, failed decryption) stays an [`anyhow::Error`] and exits with 1.
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to combine shares: {0:?}")]
+    Combine(vsss_rs::Error),
+    #[error("failed to generate a prime bigger than the secret")]
+    PrimeGeneration,
+    #[error("failed to split the secret: {0:?}")]
+    Split(vsss_rs::Error),
+    #[error("integer overflow in share arithmetic")]
+    Arithmetic,
+}
+
+impl Error {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Combine(_) => 3,
+            Self::PrimeGeneration => 4,
+            Self::Split(_) => 5,
+            Self::Arithmetic => 6,
+        }
+    }
+}
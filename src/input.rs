@@ -0,0 +1,384 @@
+//! non-interactive share input, for scripted ceremonies and tests
+//!
+//! a share file is either a json document like
+//! `{"prime": "ff:...", "shares": [{"id": 1, "value": "12:...", "checksum": "ab:cd"}]}` (which is also what `--shares-output` writes)
+//! or plain text with the prime on the first line and one `<id> <value> [checksum]` line per share.
+//! checksums are optional everywhere, but checked if present.
+//! empty lines and lines starting with `#` are ignored in the text format.
+//! share printouts of `sc-hsm-tool` (see [`parse_printout`]) and qr code images of single shares are accepted as well.
+//! any of these can be encrypted with age, see `crate::encrypt`.
+
+use std::{
+    io::{IsTerminal, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::{Identifier, U64Modulus, U64Share};
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct SharesDocument {
+    pub prime: String,
+    // the following fields are only informational when reading shares
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shares_required: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shares_total: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dkek_kcv: Option<String>,
+    pub shares: Vec<ShareEntry>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ShareEntry {
+    pub id: u64,
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    // only informational when reading shares
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custodian: Option<String>,
+}
+
+// read shares from all the given files (`-` is stdin), which all have to use the same prime
+pub fn read_shares(
+    files: &[PathBuf],
+    identity_files: &[PathBuf],
+    shares_required: usize,
+) -> anyhow::Result<(U64Modulus, Vec<U64Share>)> {
+    let identities = crate::encrypt::read_identities(identity_files)?;
+    let mut prime = None;
+    let mut shares = Vec::new();
+    for file in files {
+        let (file_prime, file_shares) = read_share_file(file, &identities)
+            .map_err(|e| anyhow::anyhow!("failed to read shares from {}: {e:#}", file.display()))?;
+        if prime.is_some_and(|prime| prime != file_prime) {
+            anyhow::bail!(
+                "the prime in {} differs from the prime in the other share files!",
+                file.display()
+            );
+        }
+        prime = Some(file_prime);
+        shares.extend(file_shares);
+    }
+    let Some(prime) = prime else {
+        anyhow::bail!("no share files given!");
+    };
+    if shares.len() < shares_required {
+        anyhow::bail!(
+            "only {} shares were read, but {shares_required} are required!",
+            shares.len()
+        );
+    }
+
+    if crate::ui::validate_prime(&prime).is_err() {
+        anyhow::bail!(
+            "the prime {} is not a prime!",
+            crate::ui::format_bigint(&prime)
+        );
+    }
+    crate::ui::validate_shares(&prime, &shares)
+        .map_err(|e| anyhow::anyhow!("the shares read are invalid! {e}"))?;
+
+    let modulus = U64Modulus::new(&prime);
+    let shares = shares
+        .into_iter()
+        .map(|(id, value)| U64Share {
+            identifier: Identifier::new(&crypto_bigint::U64::from_u64(id), modulus),
+            value: Identifier::new(&value, modulus),
+        })
+        .collect();
+    Ok((modulus, shares))
+}
+
+pub type ParsedShares = (crypto_bigint::U64, Vec<(u64, crypto_bigint::U64)>);
+
+fn read_share_file(
+    file: &Path,
+    identities: &[Box<dyn age::Identity>],
+) -> anyhow::Result<ParsedShares> {
+    let mut content = Vec::new();
+    if file == Path::new("-") {
+        let mut stdin = std::io::stdin();
+        if stdin.is_terminal() {
+            anyhow::bail!("refusing to read shares from a terminal, pipe them in instead");
+        }
+        stdin.read_to_end(&mut content)?;
+    } else {
+        std::fs::File::open(file)?.read_to_end(&mut content)?;
+    }
+
+    if crate::encrypt::is_encrypted(&content) {
+        content = crate::encrypt::decrypt(&content, identities, || {
+            crate::ui::get_passphrase(&format!("enter the passphrase for {}", file.display()))
+        })?;
+    }
+    let content = if crate::qr::is_image(&content) {
+        crate::qr::decode_image(&content)?
+    } else {
+        String::from_utf8(content)?
+    };
+    if content.trim_start().starts_with('{') {
+        parse_json(&content)
+    } else if is_printout(&content) {
+        parse_printout(&content)
+    } else {
+        parse_text(&content)
+    }
+}
+
+fn parse_json(content: &str) -> anyhow::Result<ParsedShares> {
+    let document: SharesDocument = serde_json::from_str(content)?;
+    let prime = crate::ui::parse_hex_string(&document.prime)
+        .map_err(|e| anyhow::anyhow!("invalid prime: {e}"))?;
+    let shares = document
+        .shares
+        .into_iter()
+        .map(|share| {
+            let value = crate::ui::parse_hex_string(&share.value)
+                .map_err(|e| anyhow::anyhow!("invalid value of share id {}: {e}", share.id))?;
+            if let Some(checksum) = &share.checksum {
+                crate::checksum::verify(&prime, share.id, &value, checksum).map_err(|e| {
+                    anyhow::anyhow!("invalid checksum of share id {}: {e}", share.id)
+                })?;
+            }
+            Ok((share.id, value))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok((prime, shares))
+}
+
+fn parse_text(content: &str) -> anyhow::Result<ParsedShares> {
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+    let Some((line_number, line)) = lines.next() else {
+        anyhow::bail!("file is empty");
+    };
+    let prime = crate::ui::parse_hex_string(line)
+        .map_err(|e| anyhow::anyhow!("line {line_number}: invalid prime: {e}"))?;
+
+    let shares = lines
+        .map(|(line_number, line)| {
+            let (id, value, checksum) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [id, value] => (id, value, None),
+                [id, value, checksum] => (id, value, Some(checksum)),
+                _ => anyhow::bail!(
+                    "line {line_number}: expected a share id, a share value and an optional checksum"
+                ),
+            };
+            let id = id.parse::<u64>().map_err(|_| {
+                anyhow::anyhow!(
+                    "line {line_number}: invalid share id: {}",
+                    crate::ui::InputValidationError::InvalidInteger
+                )
+            })?;
+            let value = crate::ui::parse_hex_string(value)
+                .map_err(|e| anyhow::anyhow!("line {line_number}: invalid share value: {e}"))?;
+            if let Some(checksum) = checksum {
+                crate::checksum::verify(&prime, id, &value, checksum)
+                    .map_err(|e| anyhow::anyhow!("line {line_number}: invalid checksum: {e}"))?;
+            }
+            Ok((id, value))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok((prime, shares))
+}
+
+enum PrintoutField<'a> {
+    Prime(&'a str),
+    ShareId(&'a str),
+    ShareValue(&'a str),
+    Checksum(&'a str),
+}
+
+fn printout_field(line: &str) -> Option<PrintoutField<'_>> {
+    let (key, value) = line.split_once(':')?;
+    match key.trim().to_ascii_lowercase().as_str() {
+        "prime" => Some(PrintoutField::Prime(value)),
+        "share id" => Some(PrintoutField::ShareId(value)),
+        "share value" => Some(PrintoutField::ShareValue(value)),
+        "checksum" => Some(PrintoutField::Checksum(value)),
+        _ => None,
+    }
+}
+
+pub fn is_printout(text: &str) -> bool {
+    text.lines().any(|line| printout_field(line).is_some())
+}
+
+// `sc-hsm-tool` prints every share as a block like this, everything except these lines is ignored:
+//
+// Share 1 of 3
+//
+// Prime       : ff:ff:ff:ff:ff:ff:ff:c5
+// Share ID    : 1
+// Share value : 12:34:56:78:9a:bc:de:f0
+//
+// the shares shown by this tool look the same, but have an additional `Checksum` line after the value
+pub fn parse_printout(text: &str) -> anyhow::Result<ParsedShares> {
+    let mut prime = None;
+    let mut id = None;
+    let mut shares = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        match printout_field(line) {
+            Some(PrintoutField::Prime(value)) => {
+                let value = parse_printout_hex(value)
+                    .map_err(|e| anyhow::anyhow!("line {line_number}: invalid prime: {e}"))?;
+                if prime.is_some_and(|prime| prime != value) {
+                    anyhow::bail!("line {line_number}: the prime differs from the one before");
+                }
+                prime = Some(value);
+            }
+            Some(PrintoutField::ShareId(value)) => {
+                id = Some(value.trim().parse::<u64>().map_err(|_| {
+                    anyhow::anyhow!(
+                        "line {line_number}: invalid share id: {}",
+                        crate::ui::InputValidationError::InvalidInteger
+                    )
+                })?);
+            }
+            Some(PrintoutField::ShareValue(value)) => {
+                let Some(id) = id.take() else {
+                    anyhow::bail!("line {line_number}: share value without a share id before it");
+                };
+                let value = parse_printout_hex(value)
+                    .map_err(|e| anyhow::anyhow!("line {line_number}: invalid share value: {e}"))?;
+                shares.push((id, value));
+            }
+            Some(PrintoutField::Checksum(checksum)) => {
+                let (Some(prime), Some((id, value)), None) = (prime, shares.last(), id) else {
+                    anyhow::bail!("line {line_number}: checksum without a share before it");
+                };
+                crate::checksum::verify(&prime, *id, value, checksum)
+                    .map_err(|e| anyhow::anyhow!("line {line_number}: invalid checksum: {e}"))?;
+            }
+            None => (),
+        }
+    }
+    let Some(prime) = prime else {
+        anyhow::bail!("no prime found");
+    };
+    Ok((prime, shares))
+}
+
+fn parse_printout_hex(value: &str) -> Result<crypto_bigint::U64, crate::ui::InputValidationError> {
+    // sc-hsm-tool prints big numbers without leading zero bytes (or with an additional one)
+    let digits = value
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':')
+        .collect::<String>();
+    crate::ui::parse_hex_string(format!("{:0>16}", digits.trim_start_matches('0')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the layout `sc-hsm-tool --create-dkek-share --pwd-shares-threshold` shows the shares in (big numbers as
+    // uppercase bytes without leading zero bytes), with the shares of `tests/data/shares.json`
+    const PRINTOUT: &str = "\
+Share 1 of 3
+
+
+Prime       : BC:76:8F:0F:E2:33:65:29
+Share ID    : 1
+Share value : 62:90:69:DA:90:78:72:E0
+
+";
+
+    const PRINTOUTS: &str = "\
+Share 2 of 3
+
+
+Prime       : BC:76:8F:0F:E2:33:65:29
+Share ID    : 2
+Share value : 1E:10:68:AF:B6:EA:99:B4
+
+
+Share 3 of 3
+
+
+Prime       : BC:76:8F:0F:E2:33:65:29
+Share ID    : 3
+Share value : 96:06:F6:94:BF:90:25:B1
+
+";
+
+    const PRIME: u64 = 0xbc76_8f0f_e233_6529;
+
+    fn u64(value: u64) -> crypto_bigint::U64 {
+        crypto_bigint::U64::from_u64(value)
+    }
+
+    #[test]
+    fn parses_single_block() {
+        let (prime, shares) = parse_printout(PRINTOUT).unwrap();
+        assert_eq!(prime, u64(PRIME));
+        assert_eq!(shares, [(1, u64(0x6290_69da_9078_72e0))]);
+    }
+
+    #[test]
+    fn parses_multiple_blocks() {
+        let (prime, shares) = parse_printout(PRINTOUTS).unwrap();
+        assert_eq!(prime, u64(PRIME));
+        assert_eq!(
+            shares,
+            [
+                (2, u64(0x1e10_68af_b6ea_99b4)),
+                (3, u64(0x9606_f694_bf90_25b1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_dropped_leading_zero_bytes() {
+        let printout = PRINTOUT.replace("62:90:69:DA:90:78:72:E0", "69:DA:90:78:72:E0");
+        let (_, shares) = parse_printout(&printout).unwrap();
+        assert_eq!(shares, [(1, u64(0x0000_69da_9078_72e0))]);
+    }
+
+    #[test]
+    fn parses_extra_zero_byte() {
+        let printout = PRINTOUT.replace("BC:76:8F:0F:E2:33:65:29", "00:BC:76:8F:0F:E2:33:65:29");
+        let (prime, _) = parse_printout(&printout).unwrap();
+        assert_eq!(prime, u64(PRIME));
+    }
+
+    #[test]
+    fn rejects_too_long_value() {
+        let printout = PRINTOUT.replace("62:90:69:DA:90:78:72:E0", "01:62:90:69:DA:90:78:72:E0");
+        assert!(parse_printout(&printout).is_err());
+    }
+
+    #[test]
+    fn rejects_mismatched_prime() {
+        let printouts = PRINTOUTS.replacen("BC:76:8F:0F:E2:33:65:29", "FF:FF:FF:FF:FF:FF:FF:C5", 1);
+        let error = parse_printout(&printouts).unwrap_err();
+        assert!(error.to_string().contains("the prime differs"), "{error}");
+    }
+
+    #[test]
+    fn checks_checksum() {
+        let printout = PRINTOUT.replace("72:E0\n", "72:E0\nChecksum    : 59:2d\n");
+        assert!(parse_printout(&printout).is_ok());
+        let printout = PRINTOUT.replace("72:E0\n", "72:E0\nChecksum    : 59:2e\n");
+        assert!(parse_printout(&printout).is_err());
+    }
+
+    #[test]
+    fn rejects_value_without_id() {
+        assert!(parse_printout(&PRINTOUT.replace("Share ID    : 1\n", "")).is_err());
+    }
+
+    #[test]
+    fn detects_printout() {
+        assert!(is_printout(PRINTOUT));
+        assert!(!is_printout(
+            "bc:76:8f:0f:e2:33:65:29\n1 62:90:69:da:90:78:72:e0\n"
+        ));
+    }
+}
@@ -1,15 +1,26 @@
-use std::{
-    io::Read,
-    num::NonZero,
-    path::{Path, PathBuf},
-};
+use std::{path::Path, process::ExitCode};
+
+use crypto_bigint::{modular::runtime_mod::DynResidueParams, Encoding, RandomMod};
+use rand::RngCore;
 
-use aes::cipher::{BlockDecryptMut, KeyIvInit};
-use crypto_bigint::{modular::runtime_mod::DynResidueParams, Encoding};
-use vsss_rs::ReadableShareSet;
+use crate::args::{
+    Args, CreateArgs, InspectArgs, RecoverArgs, ReshareArgs, RotateArgs, SplitArgs, VerifyArgs,
+};
 
+mod args;
+mod cards;
+mod checksum;
+mod dkek;
 mod dynresidue;
+mod encrypt;
+mod error;
+mod input;
+mod output;
+mod qr;
+mod recovery;
+mod roster;
 mod ui;
+mod words;
 
 type Identifier<const LIMBS: usize> = crate::dynresidue::IdentifierDynResidue<LIMBS>;
 type U64Modulus =
@@ -18,165 +29,309 @@ type U64Share = vsss_rs::DefaultShare<
     Identifier<{ crypto_bigint::U64::LIMBS }>,
     Identifier<{ crypto_bigint::U64::LIMBS }>,
 >;
-type Decryptor = cbc::Decryptor<aes::Aes256>;
-type EncryptionKey = [u8; 32];
-type EncryptionIv = [u8; 16];
-type Dkek = [u8; 32];
 
 // these values are just taken from the sc-hsm-tool source code
 const MAX_PRIME_ITER: usize = 1000;
-const KDF_ITERATIONS: usize = 10_000_000;
-const MAGIC: &str = "Salted__";
-
-fn build_args() -> clap::Command {
-    clap::Command::new("sc-hsm-recrypt")
-        .arg(
-            clap::Arg::new("file")
-                .required(true)
-                .help("path to the dkek share file")
-                .long("file")
-                .short('f')
-                .value_parser(clap::builder::PathBufValueParser::new()),
-        )
-        .arg(
-            clap::Arg::new("shares-total")
-                .required(true)
-                .help("total number of shares")
-                .long("shares-total")
-                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(2..)),
-        )
-        .arg(
-            clap::Arg::new("shares-required")
-                .required(true)
-                .help("minimum required number of shares")
-                .long("shares-required")
-                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(2..)),
-        )
-}
-
-struct Args {
-    dkek_file: PathBuf,
-    shares_total: usize,
-    shares_required: usize,
-}
-
-fn main() -> anyhow::Result<()> {
-    let matches = build_args().get_matches();
-    let dkek_file = matches
-        .get_one::<PathBuf>("file")
-        .expect("required arg")
-        .clone();
-    if !dkek_file.exists() {
-        anyhow::bail!("specified dkek file does not exist!");
-    }
-    if !dkek_file.is_file() {
-        anyhow::bail!("specified dkek file is not a file!");
-    }
-    let shares_total = *matches
-        .get_one::<usize>("shares-total")
-        .expect("required arg");
-    let shares_required = *matches
-        .get_one::<usize>("shares-required")
-        .expect("required arg");
-    if shares_required > shares_total {
-        anyhow::bail!(
-            "required number of shares must be less than or equal to total number of shares!"
-        );
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::from(exit_code(&e))
+        }
     }
-    let args = Args {
-        dkek_file,
-        shares_total,
-        shares_required,
+}
+
+// the typed errors can end up anywhere in the chain
+fn typed_error(e: &anyhow::Error) -> Option<&crate::error::Error> {
+    e.chain()
+        .find_map(|e| e.downcast_ref::<crate::error::Error>())
+}
+
+fn exit_code(e: &anyhow::Error) -> u8 {
+    typed_error(e).map_or(1, crate::error::Error::exit_code)
+}
+
+fn run() -> anyhow::Result<()> {
+    let args = crate::args::parse()?;
+    // the terminal ui is only needed to enter shares or to show the new ones. without it everything runs
+    // without waiting for enter, so it can be scripted
+    let terminal = match &args {
+        Args::Rotate(RotateArgs { recover, split, .. })
+        | Args::Reshare(ReshareArgs { recover, split }) => {
+            recover.shares_from.is_empty() || shows_shares(split)
+        }
+        Args::Verify(VerifyArgs { recover }) => recover.shares_from.is_empty(),
+        Args::Create(CreateArgs { split, .. }) => shows_shares(split),
+        Args::Inspect(_) => false,
     };
 
-    crate::ui::init_term();
+    if terminal {
+        crate::ui::init_term()?;
+    }
     let result = main_result(args);
-    crate::ui::restore_term();
-    result
+    if terminal {
+        crate::ui::restore_term();
+    }
+    if let Some(stdout) = result? {
+        print!("{stdout}");
+    }
+    Ok(())
 }
 
-fn main_result(args: Args) -> anyhow::Result<()> {
-    let (_, shares) = crate::ui::get_shares(args.shares_required)?;
-    println!("decrypting share...\r");
-    let result = ReadableShareSet::combine(&shares).unwrap();
-    let secret = result.retrieve();
+// returns what should be printed to stdout once the terminal is restored
+fn main_result(args: Args) -> anyhow::Result<Option<String>> {
+    match args {
+        Args::Rotate(args) => rotate(args),
+        Args::Reshare(args) => reshare(args),
+        Args::Verify(args) => verify(args).map(|()| None),
+        Args::Create(args) => create(args),
+        Args::Inspect(args) => inspect(args).map(|()| None),
+    }
+}
 
-    // decrypt the dkek backup to make sure we got the correct secret
-    let _dkek = match decrypt_dkek(&args.dkek_file, &secret.to_be_bytes()) {
-        Ok(dkek) => dkek,
-        Err(e) => {
-            anyhow::bail!("failed to decrypt: {e:?}\npossibly the entered share values are wrong?");
+// collect shares from the user and use them to decrypt the dkek share file
+fn recover_dkek(args: &RecoverArgs) -> anyhow::Result<(crypto_bigint::U64, crate::dkek::Dkek)> {
+    let dkek_file = crate::dkek::read_dkek_file(&args.dkek_file)?;
+    let (modulus, shares) = if args.shares_from.is_empty() {
+        crate::ui::get_shares(args.shares_required, args.roster.as_deref())?
+    } else {
+        crate::input::read_shares(&args.shares_from, &args.identities, args.shares_required)?
+    };
+    let recovered = match crate::ui::run_with_progress(|progress| {
+        crate::recovery::recover(&dkek_file, modulus, &shares, args.shares_required, progress)
+    })? {
+        Ok(recovered) => recovered,
+        // a typo can't cause these, so searching for one would only hide them
+        Err(e) if typed_error(&e).is_some() => return Err(e),
+        Err(e) if args.typo_recovery => {
+            let index = crate::ui::select_share(
+                &format!("{e:#}\n\nwhich share should be searched for a typo?"),
+                &shares,
+                args.roster.as_deref(),
+            )?;
+            let (recovered, share, typo) = crate::ui::run_with_progress(|progress| {
+                crate::recovery::search_typo(&dkek_file, modulus, &shares, index, progress)
+            })??;
+            crate::ui::show_message(&format!(
+                    "found a typo in share id {}: {typo}\n\nentered share value : {}\ncorrect share value : {}",
+                    crate::roster::describe(args.roster.as_deref(), share.identifier.retrieve()?.as_words()[0]),
+                    crate::ui::format_bytes(&shares[index].value.retrieve()?.to_be_bytes()),
+                    crate::ui::format_bytes(&share.value.retrieve()?.to_be_bytes()),
+                ))?;
+            recovered
         }
+        Err(e) => return Err(e),
     };
+    if !recovered.bad_share_ids.is_empty() {
+        let ids = recovered
+            .bad_share_ids
+            .iter()
+            .map(|id| crate::roster::describe(args.roster.as_deref(), *id))
+            .collect::<Vec<_>>()
+            .join(", ");
+        crate::ui::show_message(&format!(
+            "the dkek share file was decrypted, but some shares are inconsistent with the others\n\ninconsistent share ids : {ids}\n\nthese shares were ignored, make sure to tell their custodians"
+        ))?;
+    }
+    Ok((recovered.secret, recovered.dkek))
+}
+
+// encrypt the dkek with a new salt and read the written file back to make sure it decrypts to the same dkek again
+fn write_dkek_file(
+    file: &Path,
+    dkek: &crate::dkek::Dkek,
+    secret: &crypto_bigint::U64,
+    rng: &mut (impl rand::RngCore + rand::CryptoRng),
+) -> anyhow::Result<()> {
+    let mut salt = crate::dkek::Salt::default();
+    rng.fill_bytes(&mut salt);
+    crate::ui::run_with_progress(|progress| {
+        progress.start("encrypting share", 1);
+        let dkek_file = crate::dkek::encrypt_dkek(dkek, &secret.to_be_bytes(), &salt, progress)?;
+        crate::dkek::write_dkek_file(file, &dkek_file)?;
+
+        progress.start("checking written share", 1);
+        let written = crate::dkek::read_dkek_file(file)?;
+        if crate::dkek::decrypt_dkek(&written, &secret.to_be_bytes(), progress)? != *dkek {
+            anyhow::bail!("the written dkek share file does not decrypt to the same dkek!");
+        }
+        Ok(())
+    })?
+}
+
+// a new dkek share file is useless without its shares, so it is removed again if they can't be handed out
+fn remove_on_error<T>(file: &Path, f: impl FnOnce() -> anyhow::Result<T>) -> anyhow::Result<T> {
+    f().map_err(|e| match std::fs::remove_file(file) {
+        Ok(()) => e.context(format!(
+            "the new dkek share file {} was removed again",
+            file.display()
+        )),
+        Err(remove) => e.context(format!(
+            "failed to remove the new dkek share file {}, delete it by hand: {remove}",
+            file.display()
+        )),
+    })
+}
+
+fn show_kcv(message: &str, dkek: &crate::dkek::Dkek) -> anyhow::Result<()> {
+    crate::ui::show_message(&format!(
+        "{message}\n\ndkek kcv : {}\n\ncompare this with the kcv shown by the smart card hsm after importing the dkek share",
+        crate::ui::format_bytes(&crate::dkek::kcv(dkek))
+    ))
+}
+
+fn show_policy(
+    old_shares_required: usize,
+    split: &SplitArgs,
+    old_shares_stay_valid: bool,
+) -> anyhow::Result<()> {
+    let mut message = format!(
+        "old policy : {old_shares_required} shares required\nnew policy : {} of {} shares required",
+        split.shares_required, split.shares_total
+    );
+    if old_shares_stay_valid && split.shares_required > old_shares_required {
+        message.push_str(&format!(
+            "\n\nWARNING: the old shares stay valid, so {old_shares_required} old shares are still enough to decrypt the dkek share file!\nuse rotate instead to actually require more shares"
+        ));
+    }
+    crate::ui::show_message(&message)
+}
+
+fn rotate(args: RotateArgs) -> anyhow::Result<Option<String>> {
+    let (_, dkek) = recover_dkek(&args.recover)?;
+    show_kcv("the dkek share file was decrypted successfully", &dkek)?;
+    show_policy(args.recover.shares_required, &args.split, false)?;
 
     let mut rng = rand::rngs::OsRng;
-    // TODO: generate a new secret and reencrypt the dkek?
-    let new_prime = generate_prime_min_with_rng(&mut rng, &secret).unwrap();
-    let new_modulus = DynResidueParams::new(&new_prime);
-    let shares = vsss_rs::shamir::split_secret_with_participant_generator::<U64Share>(
-        args.shares_required,
-        args.shares_total,
-        &Identifier::new(&secret, new_modulus),
-        &mut rng,
-        &[vsss_rs::ParticipantIdGeneratorType::sequential(
-            Some(Identifier::new(&crypto_bigint::U64::ONE, new_modulus)),
-            Some(Identifier::new(&crypto_bigint::U64::ONE, new_modulus)),
-            NonZero::new(args.shares_total).unwrap(),
-        )],
-    )
-    .unwrap();
+    let (secret, prime) = generate_secret_with_rng(&mut rng);
+    let (modulus, shares) = split_secret(&secret, &prime, &args.split, &mut rng)?;
+    write_dkek_file(&args.output_file, &dkek, &secret, &mut rng)?;
+    remove_on_error(&args.output_file, || {
+        show_kcv("the re-encrypted dkek share file was written", &dkek)?;
+        output_shares(&modulus, &shares, &args.split, &dkek)
+    })
+}
+
+fn reshare(args: ReshareArgs) -> anyhow::Result<Option<String>> {
+    let (secret, dkek) = recover_dkek(&args.recover)?;
+    show_kcv("the dkek share file was decrypted successfully", &dkek)?;
+    show_policy(args.recover.shares_required, &args.split, true)?;
+
+    let mut rng = rand::rngs::OsRng;
+    let prime = generate_prime_min_with_rng(&mut rng, &secret)
+        .ok_or(crate::error::Error::PrimeGeneration)?;
+    let (modulus, shares) = split_secret(&secret, &prime, &args.split, &mut rng)?;
+    output_shares(&modulus, &shares, &args.split, &dkek)
+}
+
+fn verify(args: VerifyArgs) -> anyhow::Result<()> {
+    let (_, dkek) = recover_dkek(&args.recover)?;
+    show_kcv("the dkek share file was decrypted successfully", &dkek)?;
+
+    Ok(())
+}
 
-    crate::ui::print_shares(new_modulus.modulus(), &shares)?;
+fn create(args: CreateArgs) -> anyhow::Result<Option<String>> {
+    let mut rng = rand::rngs::OsRng;
+    let mut dkek = crate::dkek::Dkek::default();
+    rng.fill_bytes(&mut dkek);
+    let (secret, prime) = generate_secret_with_rng(&mut rng);
+    let (modulus, shares) = split_secret(&secret, &prime, &args.split, &mut rng)?;
+    write_dkek_file(&args.output_file, &dkek, &secret, &mut rng)?;
+    remove_on_error(&args.output_file, || {
+        show_kcv("the new dkek share file was written", &dkek)?;
+        output_shares(&modulus, &shares, &args.split, &dkek)
+    })
+}
 
+fn inspect(args: InspectArgs) -> anyhow::Result<()> {
+    let dkek_file = crate::dkek::read_dkek_file(&args.dkek_file)?;
+    let salt = crate::dkek::salt(&dkek_file)?;
+    println!("file   : {}", args.dkek_file.display());
+    println!("format : sc-hsm-tool dkek share (aes-256-cbc, md5 EVP_BytesToKey)");
+    println!("salt   : {}", crate::ui::format_bytes(&salt));
     Ok(())
 }
 
-fn decrypt_dkek<P: AsRef<Path>>(file: P, share_secret: &[u8; 8]) -> anyhow::Result<Dkek> {
-    let mut file = std::fs::File::open(file)?;
-    let mut bytes = [0; 64]; // dkek files are always 64 bytes long
-    file.read_exact(&mut bytes)?;
-    if &bytes[0..8] != MAGIC.as_bytes() {
-        anyhow::bail!("dkek file doesn't start with the correct header!");
+// whether the new shares are shown on the screen one by one, instead of being written somewhere
+fn shows_shares(split: &SplitArgs) -> bool {
+    split.shares_output.is_none() && split.encrypt.is_none()
+}
+
+fn output_shares(
+    modulus: &U64Modulus,
+    shares: &[U64Share],
+    split: &SplitArgs,
+    dkek: &crate::dkek::Dkek,
+) -> anyhow::Result<Option<String>> {
+    if let Some(dir) = &split.qr_output {
+        crate::output::write_qr_codes(dir, modulus.modulus(), shares, split)?;
     }
-    let salt = &bytes[8..16];
-    let mut data = [0; 48];
-    data.copy_from_slice(&bytes[16..]);
-
-    let (key, iv) = derive_key_iv(salt, share_secret);
-    let dec = Decryptor::new(&key.into(), &iv.into())
-        .decrypt_padded_mut::<cbc::cipher::block_padding::Pkcs7>(&mut data)?;
-    let mut data = Dkek::default();
-    data.copy_from_slice(dec);
-
-    Ok(data)
-}
-
-// this is an impl of openssl's EVP_BytesToKey (according to the docs at least)
-// but specifically for aes_256_cbc/md5/10_000_000 iterations
-fn derive_key_iv(salt: &[u8], secret: &[u8; 8]) -> (EncryptionKey, EncryptionIv) {
-    debug_assert!(salt.len() == 8);
-    fn hash(previous: &[u8], salt: &[u8], secret: &[u8; 8]) -> [u8; 16] {
-        debug_assert!(previous.len() <= 16);
-        let mut full_data = [0_u8; 32];
-        (&mut full_data[0..previous.len()]).copy_from_slice(previous);
-        (&mut full_data[previous.len()..previous.len() + 8]).copy_from_slice(secret);
-        (&mut full_data[previous.len() + 8..previous.len() + 16]).copy_from_slice(salt);
-
-        let mut hash = *md5::compute(&full_data[..previous.len() + 16]);
-        for _ in 1..KDF_ITERATIONS {
-            hash = *md5::compute(hash);
+    if let Some(dir) = &split.cards_output {
+        crate::cards::write_cards(dir, modulus.modulus(), shares, split, dkek)?;
+    }
+    let stdout = match (&split.shares_output, &split.encrypt) {
+        (Some(file), _) => {
+            let document = crate::output::shares_document(modulus.modulus(), shares, split, dkek)?;
+            crate::output::write_json(file, &document)?
+        }
+        (None, Some(encrypt)) => {
+            crate::output::write_encrypted_shares(encrypt, modulus.modulus(), shares, split, dkek)?;
+            crate::ui::show_message(&format!(
+                "the encrypted shares were written to {}",
+                encrypt.output_dir.display()
+            ))?;
+            None
+        }
+        (None, None) => {
+            crate::ui::print_shares(modulus.modulus(), shares, split)?;
+            None
         }
-        hash
+    };
+    if let Some(roster) = &split.roster {
+        let register = shares
+            .iter()
+            .map(|share| {
+                let id = share.identifier.retrieve()?.as_words()[0];
+                let name = crate::roster::name(Some(roster), id).unwrap_or_default();
+                Ok(format!("share id {id:>3} : {name}"))
+            })
+            .collect::<Result<Vec<_>, crate::error::Error>>()?
+            .join("\n");
+        crate::ui::show_message(&format!(
+            "custody register (which custodian holds which share):\n\n{register}"
+        ))?;
     }
+    Ok(stdout)
+}
 
-    let d1 = hash(&[], salt, secret);
-    let d2 = hash(&d1, salt, secret);
-    let d3 = hash(&d2, salt, secret);
-
-    let mut key = [0_u8; 32];
-    (&mut key[0..16]).copy_from_slice(&d1);
-    (&mut key[16..32]).copy_from_slice(&d2);
-    (key, d3)
+fn split_secret(
+    secret: &crypto_bigint::U64,
+    prime: &crypto_bigint::U64,
+    split: &SplitArgs,
+    rng: &mut (impl rand::RngCore + rand::CryptoRng),
+) -> Result<(U64Modulus, Vec<U64Share>), crate::error::Error> {
+    let modulus = DynResidueParams::new(prime);
+    let shares = vsss_rs::shamir::split_secret_with_participant_generator::<U64Share>(
+        split.shares_required,
+        split.shares_total.get(),
+        &Identifier::new(secret, modulus),
+        rng,
+        &[vsss_rs::ParticipantIdGeneratorType::sequential(
+            Some(Identifier::new(&crypto_bigint::U64::ONE, modulus)),
+            Some(Identifier::new(&crypto_bigint::U64::ONE, modulus)),
+            split.shares_total,
+        )],
+    )
+    .map_err(crate::error::Error::Split)?;
+    // fail here instead of writing out shares that can't be shown
+    for share in &shares {
+        share.identifier.retrieve()?;
+        share.value.retrieve()?;
+    }
+    Ok((modulus, shares))
 }
 
 // generate a prime bigger than the given secret we want to encode
@@ -193,3 +348,61 @@ fn generate_prime_min_with_rng<const LIMBS: usize>(
     }
     None
 }
+
+// generate a new random share secret (the password for the dkek share file) together with a prime to split it with
+fn generate_secret_with_rng<const LIMBS: usize>(
+    rng: &mut (impl rand::RngCore + rand::CryptoRng),
+) -> (crypto_bigint::Uint<LIMBS>, crypto_bigint::Uint<LIMBS>) {
+    // the prime is generated first so the secret can just be drawn below it
+    let prime =
+        crypto_primes::generate_prime_with_rng(rng, Some(crypto_bigint::Uint::<LIMBS>::BITS));
+    let modulus = crypto_bigint::NonZero::new(prime).expect("primes are never zero");
+    loop {
+        let secret = crypto_bigint::Uint::<LIMBS>::random_mod(rng, &modulus);
+        // sc-hsm-tool turns the combined secret back into the password with `BN_bn2bin`, which drops leading zero
+        // bytes. a secret below 2^56 would be a shorter password there than the 8 bytes the file is encrypted with
+        if secret.bits() > crypto_bigint::Uint::<LIMBS>::BITS - 8 {
+            return (secret, prime);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_have_no_leading_zero_byte() {
+        let mut rng = rand::rngs::OsRng;
+        for _ in 0..1000 {
+            let (secret, prime) =
+                generate_secret_with_rng::<{ crypto_bigint::U64::LIMBS }>(&mut rng);
+            assert_ne!(secret.to_be_bytes()[0], 0);
+            assert!(secret < prime);
+        }
+    }
+
+    // the codes of the table in the readme
+    #[test]
+    fn exit_codes() {
+        let errors = [
+            (anyhow::anyhow!("wrong shares"), 1),
+            (
+                crate::error::Error::Combine(vsss_rs::Error::SharingMinThreshold).into(),
+                3,
+            ),
+            (crate::error::Error::PrimeGeneration.into(), 4),
+            (
+                crate::error::Error::Split(vsss_rs::Error::SharingMinThreshold).into(),
+                5,
+            ),
+            (crate::error::Error::Arithmetic.into(), 6),
+        ];
+        for (e, code) in errors {
+            assert_eq!(exit_code(&e), code, "{e}");
+            // also with context around it
+            let e = e.context("failed to recover the dkek");
+            assert_eq!(exit_code(&e), code, "{e}");
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! non-interactive output of new shares, for ceremony tooling
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    args::SplitArgs,
+    dkek::Dkek,
+    encrypt::{EncryptArgs, Recipient},
+    input::{ShareEntry, SharesDocument},
+    U64Share,
+};
+
+pub fn shares_document(
+    modulus: &crypto_bigint::U64,
+    shares: &[U64Share],
+    split: &SplitArgs,
+    dkek: &Dkek,
+) -> Result<SharesDocument, crate::error::Error> {
+    Ok(SharesDocument {
+        dkek_kcv: Some(crate::ui::format_bytes(&crate::dkek::kcv(dkek))),
+        ..document(modulus, shares, split)?
+    })
+}
+
+// the document of a single share, as contained in its qr code
+pub fn share_document(
+    modulus: &crypto_bigint::U64,
+    share: &U64Share,
+    split: &SplitArgs,
+) -> Result<SharesDocument, crate::error::Error> {
+    document(modulus, std::slice::from_ref(share), split)
+}
+
+fn document(
+    modulus: &crypto_bigint::U64,
+    shares: &[U64Share],
+    split: &SplitArgs,
+) -> Result<SharesDocument, crate::error::Error> {
+    Ok(SharesDocument {
+        prime: crate::ui::format_bigint(modulus),
+        shares_required: Some(split.shares_required),
+        shares_total: Some(split.shares_total.get()),
+        dkek_kcv: None,
+        shares: shares
+            .iter()
+            .map(|share| {
+                let id = share.identifier.retrieve()?.as_words()[0];
+                let value = share.value.retrieve()?;
+                Ok(ShareEntry {
+                    id,
+                    value: crate::ui::format_bigint(&value),
+                    checksum: Some(crate::checksum::format_checksum(modulus, id, &value)),
+                    custodian: crate::roster::name(split.roster.as_deref(), id).map(str::to_owned),
+                })
+            })
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+pub fn share_qr_code(
+    modulus: &crypto_bigint::U64,
+    share: &U64Share,
+    split: &SplitArgs,
+) -> anyhow::Result<qrcode::QrCode> {
+    crate::qr::encode(&serde_json::to_string(&share_document(
+        modulus, share, split,
+    )?)?)
+}
+
+// writes share-<id>.png and share-<id>.svg for every share
+pub fn write_qr_codes(
+    dir: &Path,
+    modulus: &crypto_bigint::U64,
+    shares: &[U64Share],
+    split: &SplitArgs,
+) -> anyhow::Result<()> {
+    for share in shares {
+        let id = share.identifier.retrieve()?.as_words()[0];
+        let code = share_qr_code(modulus, share, split)?;
+        let [png, svg] = qr_files(dir, id);
+        crate::qr::write_png(&png, &code)?;
+        crate::qr::write_svg(&svg, &code)?;
+    }
+    Ok(())
+}
+
+pub fn qr_files(dir: &Path, id: u64) -> [PathBuf; 2] {
+    [
+        dir.join(format!("share-{id}.png")),
+        dir.join(format!("share-{id}.svg")),
+    ]
+}
+
+// writes every share to the file of its custodian, so it is never shown on the screen
+pub fn write_encrypted_shares(
+    encrypt: &EncryptArgs,
+    modulus: &crypto_bigint::U64,
+    shares: &[U64Share],
+    split: &SplitArgs,
+    dkek: &Dkek,
+) -> anyhow::Result<()> {
+    for (share, custodian) in shares.iter().zip(&encrypt.custodians) {
+        let document = shares_document(modulus, std::slice::from_ref(share), split, dkek)?;
+        let mut json = serde_json::to_string_pretty(&document)?;
+        json.push('\n');
+        let encrypted = match &custodian.recipient {
+            Recipient::X25519(recipient) => crate::encrypt::encrypt(json.as_bytes(), recipient)?,
+            Recipient::Passphrase => {
+                let passphrase = crate::ui::get_new_passphrase(&format!(
+                    "hand the keyboard to {} to choose the passphrase for share id {}",
+                    custodian.name,
+                    share.identifier.retrieve()?.as_words()[0]
+                ))?;
+                crate::encrypt::encrypt(json.as_bytes(), &age::scrypt::Recipient::new(passphrase))?
+            }
+        };
+        write_new_file(
+            &crate::encrypt::share_file(&encrypt.output_dir, custodian),
+            &encrypted,
+        )?;
+    }
+    Ok(())
+}
+
+// writes the document to the file, or returns it if it should go to stdout (`-`) once the terminal is restored
+pub fn write_json(file: &Path, document: &SharesDocument) -> anyhow::Result<Option<String>> {
+    let mut json = serde_json::to_string_pretty(document)?;
+    json.push('\n');
+    if file == Path::new("-") {
+        return Ok(Some(json));
+    }
+    write_new_file(file, json.as_bytes())?;
+    Ok(None)
+}
+
+// never overwrites anything, written files contain shares or the dkek share file they protect
+pub fn write_new_file(file: &Path, content: &[u8]) -> anyhow::Result<()> {
+    let mut new_file = std::fs::File::create_new(file)
+        .map_err(|e| anyhow::anyhow!("failed to create {}: {e}", file.display()))?;
+    new_file
+        .write_all(content)
+        .and_then(|()| new_file.sync_all())
+        .map_err(|e| anyhow::anyhow!("failed to write {}: {e}", file.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_new_file_never_overwrites() {
+        let file = std::env::temp_dir().join(format!("sc-hsm-recrypt-{}.txt", std::process::id()));
+        write_new_file(&file, b"first").unwrap();
+        let error = write_new_file(&file, b"second").unwrap_err();
+        let content = std::fs::read(&file);
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(content.unwrap(), b"first");
+        assert!(
+            error.to_string().contains(&file.display().to_string()),
+            "{error}"
+        );
+    }
+}
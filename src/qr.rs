@@ -0,0 +1,229 @@
+//! qr codes of single shares, so they don't have to be typed in again.
+//!
+//! every qr code contains a json document like the ones written by `--shares-output`, but with just one share.
+//! images are decoded with `rqrr`, which corrects damaged codes as far as the error correction level allows and
+//! fails otherwise, so a damaged code never returns wrong data.
+
+use std::path::Path;
+
+use qrcode::{EcLevel, QrCode};
+
+const EC_LEVEL: EcLevel = EcLevel::M;
+// pixels per module in written png images
+const PNG_MODULE_SIZE: u32 = 8;
+
+pub fn encode(payload: &str) -> anyhow::Result<QrCode> {
+    QrCode::with_error_correction_level(payload, EC_LEVEL)
+        .map_err(|e| anyhow::anyhow!("share does not fit into a qr code! {e}"))
+}
+
+// half block characters, two modules per character. the colors are inverted for terminals with a dark background
+pub fn render_terminal(code: &QrCode) -> String {
+    code.render::<qrcode::render::unicode::Dense1x2>()
+        .dark_color(qrcode::render::unicode::Dense1x2::Light)
+        .light_color(qrcode::render::unicode::Dense1x2::Dark)
+        .build()
+}
+
+pub fn write_png(file: &Path, code: &QrCode) -> anyhow::Result<()> {
+    let image = code
+        .render::<image::Luma<u8>>()
+        .module_dimensions(PNG_MODULE_SIZE, PNG_MODULE_SIZE)
+        .build();
+    crate::output::write_new_file(file, &encode_png(&image)?)
+}
+
+fn encode_png(image: &image::GrayImage) -> anyhow::Result<Vec<u8>> {
+    let mut png = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut png, image::ImageFormat::Png)?;
+    Ok(png.into_inner())
+}
+
+pub fn write_svg(file: &Path, code: &QrCode) -> anyhow::Result<()> {
+    let svg = code
+        .render::<qrcode::render::svg::Color>()
+        .min_dimensions(200, 200)
+        .build();
+    crate::output::write_new_file(file, svg.as_bytes())
+}
+
+pub fn is_image(content: &[u8]) -> bool {
+    image::guess_format(content).is_ok()
+}
+
+// returns the text contained in the qr code of the image
+pub fn decode_image(content: &[u8]) -> anyhow::Result<String> {
+    let image = image::load_from_memory(content)?.into_luma8();
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(
+        image.width() as usize,
+        image.height() as usize,
+        |x, y| image.get_pixel(x as u32, y as u32).0[0],
+    );
+    match &prepared.detect_grids()[..] {
+        [grid] => {
+            let (_, text) = grid
+                .decode()
+                .map_err(|e| anyhow::anyhow!("failed to decode the qr code: {e}"))?;
+            Ok(text)
+        }
+        [] => anyhow::bail!("no qr code found in the image"),
+        _ => anyhow::bail!("the image contains more than one qr code"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use qrcode::{
+        bits::Bits,
+        canvas::{Canvas, MaskPattern},
+        Version,
+    };
+    use rand::{Rng, SeedableRng};
+
+    const MASKS: [MaskPattern; 8] = [
+        MaskPattern::Checkerboard,
+        MaskPattern::HorizontalLines,
+        MaskPattern::VerticalLines,
+        MaskPattern::DiagonalLines,
+        MaskPattern::LargeCheckerboard,
+        MaskPattern::Fields,
+        MaskPattern::Diamonds,
+        MaskPattern::Meadow,
+    ];
+
+    fn payload(len: usize) -> String {
+        (0..len)
+            .map(|i| char::from(b'!' + (i % 90) as u8))
+            .collect()
+    }
+
+    // a qr code of the payload in the given version with the given mask, as modules (true is dark)
+    fn modules(payload: &str, version: i16, mask: MaskPattern) -> Vec<Vec<bool>> {
+        let version = Version::Normal(version);
+        let mut bits = Bits::new(version);
+        bits.push_byte_data(payload.as_bytes()).unwrap();
+        bits.push_terminator(EC_LEVEL).unwrap();
+        let (data, ec) =
+            qrcode::ec::construct_codewords(&bits.into_bytes(), version, EC_LEVEL).unwrap();
+        let mut canvas = Canvas::new(version, EC_LEVEL);
+        canvas.draw_all_functional_patterns();
+        canvas.draw_data(&data, &ec);
+        canvas.apply_mask(mask);
+        let width = version.width();
+        (0..width)
+            .map(|y| (0..width).map(|x| canvas.get(x, y).is_dark()).collect())
+            .collect()
+    }
+
+    // png with a quiet zone of 4 modules
+    fn png(modules: &[Vec<bool>], module_size: u32) -> Vec<u8> {
+        let width = (modules.len() as u32 + 8) * module_size;
+        let image = image::GrayImage::from_fn(width, width, |x, y| {
+            let (x, y) = (x / module_size, y / module_size);
+            let dark = (4..modules.len() as u32 + 4).contains(&x)
+                && (4..modules.len() as u32 + 4).contains(&y)
+                && modules[y as usize - 4][x as usize - 4];
+            image::Luma([if dark { 0 } else { 255 }])
+        });
+        encode_png(&image).unwrap()
+    }
+
+    #[test]
+    fn round_trip_versions() {
+        let mut widths = Vec::new();
+        for len in [
+            1, 10, 30, 60, 100, 150, 250, 400, 600, 900, 1300, 1800, 2300,
+        ] {
+            let payload = payload(len);
+            let code = encode(&payload).unwrap();
+            let image = code
+                .render::<image::Luma<u8>>()
+                .module_dimensions(PNG_MODULE_SIZE, PNG_MODULE_SIZE)
+                .build();
+            assert_eq!(decode_image(&encode_png(&image).unwrap()).unwrap(), payload);
+            widths.push(code.width());
+        }
+        // from version 1 to above version 30
+        assert_eq!(widths.first(), Some(&21));
+        assert!(widths.last() > Some(&137));
+    }
+
+    #[test]
+    fn round_trip_masks() {
+        // version 7 and up have version info blocks
+        for (version, len) in [(1, 5), (7, 80), (15, 300)] {
+            let payload = payload(len);
+            for mask in MASKS {
+                let image = png(&modules(&payload, version, mask), 3);
+                assert_eq!(
+                    decode_image(&image).unwrap(),
+                    payload,
+                    "version {version}, mask {mask:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn damaged_code_is_corrected() {
+        let payload = payload(80);
+        let original = modules(&payload, 7, MaskPattern::Fields);
+        let width = original.len();
+        // the bottom right modules are the first data modules, the others are somewhere in the data or ec codewords
+        for (x, y) in [
+            (width - 1, width - 1),
+            (width - 2, width - 5),
+            (20, 30),
+            (12, 40),
+            (3, 20),
+        ] {
+            let mut damaged = original.clone();
+            damaged[y][x] = !damaged[y][x];
+            assert_eq!(
+                decode_image(&png(&damaged, 4)).unwrap(),
+                payload,
+                "module {x}, {y}"
+            );
+        }
+    }
+
+    #[test]
+    fn destroyed_code_fails() {
+        let payload = payload(80);
+        let mut damaged = modules(&payload, 7, MaskPattern::Fields);
+        // the data below the top finder patterns, way more than the error correction can handle
+        for row in &mut damaged[10..30] {
+            for module in &mut row[9..36] {
+                *module = !*module;
+            }
+        }
+        assert!(decode_image(&png(&damaged, 4)).is_err());
+    }
+
+    #[test]
+    fn damaged_code_never_decodes_to_wrong_data() {
+        let payload = payload(80);
+        let original = modules(&payload, 7, MaskPattern::Diamonds);
+        let width = original.len();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        for damage in 1..100 {
+            let mut damaged = original.clone();
+            for _ in 0..damage {
+                let (x, y) = (rng.gen_range(0..width), rng.gen_range(0..width));
+                damaged[y][x] = !damaged[y][x];
+            }
+            if let Ok(decoded) = decode_image(&png(&damaged, 3)) {
+                assert_eq!(decoded, payload, "{damage} damaged modules");
+            }
+        }
+    }
+
+    #[test]
+    fn no_qr_code_fails() {
+        let blank = image::GrayImage::from_pixel(100, 100, image::Luma([255]));
+        assert!(decode_image(&encode_png(&blank).unwrap()).is_err());
+        assert!(decode_image(b"not an image").is_err());
+    }
+}
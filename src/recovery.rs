@@ -0,0 +1,422 @@
+//! recovering the dkek share password from possibly broken shares
+//!
+//! the kdf is really slow, so everything that has to try multiple candidates runs them on all available cores.
+
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Mutex,
+};
+
+use crypto_bigint::Encoding;
+use vsss_rs::ReadableShareSet;
+
+use crate::{
+    dkek::{Dkek, DkekFile, KdfProgress},
+    Identifier, U64Modulus, U64Share,
+};
+
+pub struct Recovered {
+    pub secret: crypto_bigint::U64,
+    pub dkek: Dkek,
+    // ids of entered shares which don't fit the shares that decrypted the dkek share file
+    pub bad_share_ids: Vec<u64>,
+}
+
+pub fn recover(
+    dkek_file: &DkekFile,
+    modulus: U64Modulus,
+    shares: &[U64Share],
+    shares_required: usize,
+    progress: &KdfProgress,
+) -> anyhow::Result<Recovered> {
+    // if all shares are fine, all of them together result in the same secret
+    progress.start("decrypting share", 1);
+    let error = match try_shares(dkek_file, shares, progress) {
+        Ok((secret, dkek)) => {
+            return Ok(Recovered {
+                secret,
+                dkek,
+                bad_share_ids: Vec::new(),
+            })
+        }
+        Err(e) => e,
+    };
+    progress.check()?;
+    // the shares can't be combined at all, other combinations won't help with that
+    if error.is::<crate::error::Error>() {
+        return Err(error);
+    }
+    if shares.len() <= shares_required {
+        anyhow::bail!("failed to decrypt: {error:#}\npossibly the entered share values are wrong?");
+    }
+
+    let subsets = combinations(shares.len(), shares_required);
+    progress.start(
+        format!(
+            "failed to decrypt with all shares, trying {} combinations of {shares_required} shares",
+            subsets.len()
+        ),
+        subsets.len(),
+    );
+    let found = find_parallel(&subsets, |subset| {
+        let shares = subset
+            .iter()
+            .map(|&i| shares[i].clone())
+            .collect::<Vec<_>>();
+        try_shares(dkek_file, &shares, progress)
+            .ok()
+            .map(|r| (subset.clone(), r))
+    });
+    progress.check()?;
+    let Some((subset, (secret, dkek))) = found else {
+        anyhow::bail!(
+            "failed to decrypt with any combination of {shares_required} shares\npossibly too many of the entered share values are wrong?"
+        );
+    };
+
+    // every share is checked against the polynomial of a working combination, no kdf needed for that
+    let good_shares = subset
+        .iter()
+        .map(|&i| shares[i].clone())
+        .collect::<Vec<_>>();
+    let bad_share_ids = bad_share_ids(&good_shares, shares, modulus)?;
+
+    Ok(Recovered {
+        secret,
+        dkek,
+        bad_share_ids,
+    })
+}
+
+// ids of the shares that don't lie on the polynomial going through `good_shares`
+fn bad_share_ids(
+    good_shares: &[U64Share],
+    shares: &[U64Share],
+    modulus: U64Modulus,
+) -> Result<Vec<u64>, crate::error::Error> {
+    let mut bad_share_ids = Vec::new();
+    for share in shares {
+        let expected = interpolate(good_shares, &share.identifier, modulus);
+        if expected != Some(share.value.retrieve()?) {
+            bad_share_ids.push(share.identifier.retrieve()?.as_words()[0]);
+        }
+    }
+    Ok(bad_share_ids)
+}
+
+pub enum Typo {
+    Substitution {
+        position: usize,
+        entered: char,
+        correct: char,
+    },
+    Transposition {
+        position: usize,
+    },
+}
+
+impl std::fmt::Display for Typo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // positions are counted in hex digits starting at 1, ignoring the colons
+        match self {
+            Self::Substitution {
+                position,
+                entered,
+                correct,
+            } => write!(
+                f,
+                "digit {} is '{correct}' instead of '{entered}'",
+                position + 1
+            ),
+            Self::Transposition { position } => {
+                write!(
+                    f,
+                    "digits {} and {} are swapped",
+                    position + 1,
+                    position + 2
+                )
+            }
+        }
+    }
+}
+
+// try all single digit typos in the share at `index` until one of them decrypts the dkek share file
+pub fn search_typo(
+    dkek_file: &DkekFile,
+    modulus: U64Modulus,
+    shares: &[U64Share],
+    index: usize,
+    progress: &KdfProgress,
+) -> anyhow::Result<(Recovered, U64Share, Typo)> {
+    let entered = shares[index].value.retrieve()?.as_words()[0];
+    let candidates = typo_candidates(entered)
+        .into_iter()
+        .filter(|(value, _)| crypto_bigint::U64::from_u64(*value) < *modulus.modulus())
+        .collect::<Vec<_>>();
+
+    progress.start(
+        format!("searching {} typo candidates", candidates.len()),
+        candidates.len(),
+    );
+    let found = find_parallel(&candidates, |(value, _)| {
+        let mut shares = shares.to_vec();
+        shares[index].value = Identifier::new(&crypto_bigint::U64::from_u64(*value), modulus);
+        try_shares(dkek_file, &shares, progress)
+            .ok()
+            .map(|r| (shares.swap_remove(index), r))
+    });
+    progress.check()?;
+
+    let Some((share, (secret, dkek))) = found else {
+        anyhow::bail!("failed to decrypt with any single digit correction of the share");
+    };
+    let correct = share.value.retrieve()?.as_words()[0];
+    let (_, typo) = candidates
+        .into_iter()
+        .find(|(value, _)| *value == correct)
+        .expect("found value is a candidate");
+    Ok((
+        Recovered {
+            secret,
+            dkek,
+            bad_share_ids: Vec::new(),
+        },
+        share,
+        typo,
+    ))
+}
+
+// all values that differ from `value` by one hex digit or by two swapped adjacent hex digits
+fn typo_candidates(value: u64) -> Vec<(u64, Typo)> {
+    const DIGITS: &str = "0123456789abcdef";
+    let digits = format!("{value:016x}").chars().collect::<Vec<_>>();
+    let to_value = |digits: &[char]| {
+        u64::from_str_radix(&digits.iter().collect::<String>(), 16).expect("only hex digits")
+    };
+
+    let mut candidates = Vec::new();
+    for position in 0..digits.len() {
+        for correct in DIGITS.chars().filter(|&c| c != digits[position]) {
+            let mut candidate = digits.clone();
+            candidate[position] = correct;
+            candidates.push((
+                to_value(&candidate),
+                Typo::Substitution {
+                    position,
+                    entered: digits[position],
+                    correct,
+                },
+            ));
+        }
+    }
+    for position in 0..digits.len() - 1 {
+        if digits[position] != digits[position + 1] {
+            let mut candidate = digits.clone();
+            candidate.swap(position, position + 1);
+            candidates.push((to_value(&candidate), Typo::Transposition { position }));
+        }
+    }
+    candidates
+}
+
+fn try_shares(
+    dkek_file: &DkekFile,
+    shares: &[U64Share],
+    progress: &KdfProgress,
+) -> anyhow::Result<(crypto_bigint::U64, Dkek)> {
+    let secret = ReadableShareSet::combine(&shares)
+        .map_err(crate::error::Error::Combine)?
+        .retrieve()?;
+    let dkek = crate::dkek::decrypt_dkek(dkek_file, &secret.to_be_bytes(), progress)?;
+    Ok((secret, dkek))
+}
+
+// evaluate the polynomial going through the given shares at `x`
+fn interpolate(
+    shares: &[U64Share],
+    x: &Identifier<{ crypto_bigint::U64::LIMBS }>,
+    modulus: U64Modulus,
+) -> Option<crypto_bigint::U64> {
+    let mut result = *Identifier::new(&crypto_bigint::U64::ZERO, modulus);
+    for (i, share_i) in shares.iter().enumerate() {
+        let mut basis = *Identifier::new(&crypto_bigint::U64::ONE, modulus);
+        for (j, share_j) in shares.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+            let numerator = x.sub(&share_j.identifier);
+            let denominator = share_i.identifier.sub(&share_j.identifier);
+            basis = basis.mul(&numerator.mul(&denominator.invert()?));
+        }
+        result = result.add(&basis.mul(&share_i.value));
+    }
+    result.retrieve().ok()
+}
+
+// all subsets of `k` out of `n` indices
+fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    let mut result = Vec::new();
+    let mut current = (0..k).collect::<Vec<_>>();
+    loop {
+        result.push(current.clone());
+        // find the rightmost index that can still be moved to the right
+        let Some(i) = (0..k).rev().find(|&i| current[i] < n - k + i) else {
+            return result;
+        };
+        current[i] += 1;
+        for j in i + 1..k {
+            current[j] = current[j - 1] + 1;
+        }
+    }
+}
+
+// run `f` on the candidates on all cores and return the first result found
+pub fn find_parallel<T: Sync, R: Send>(
+    candidates: &[T],
+    f: impl Fn(&T) -> Option<R> + Sync,
+) -> Option<R> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let next = AtomicUsize::new(0);
+    let done = AtomicBool::new(false);
+    let found = Mutex::new(None);
+    std::thread::scope(|scope| {
+        for _ in 0..threads.min(candidates.len()) {
+            scope.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(candidate) = candidates.get(i) else {
+                        break;
+                    };
+                    if let Some(result) = f(candidate) {
+                        done.store(true, Ordering::Relaxed);
+                        found.lock().unwrap().get_or_insert(result);
+                    }
+                }
+            });
+        }
+    });
+    found.into_inner().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use super::*;
+    use crate::args::{ShareEncoding, SplitArgs};
+
+    fn split_args(shares_total: usize, shares_required: usize) -> SplitArgs {
+        SplitArgs {
+            shares_total: NonZero::new(shares_total).unwrap(),
+            shares_required,
+            shares_output: None,
+            share_encoding: ShareEncoding::Hex,
+            qr: false,
+            qr_output: None,
+            cards_output: None,
+            encrypt: None,
+            roster: None,
+            confirm_shares: false,
+        }
+    }
+
+    #[test]
+    fn combinations_of_3_out_of_5() {
+        assert_eq!(
+            combinations(5, 3),
+            [
+                [0, 1, 2],
+                [0, 1, 3],
+                [0, 1, 4],
+                [0, 2, 3],
+                [0, 2, 4],
+                [0, 3, 4],
+                [1, 2, 3],
+                [1, 2, 4],
+                [1, 3, 4],
+                [2, 3, 4],
+            ]
+        );
+    }
+
+    #[test]
+    fn combinations_of_all() {
+        assert_eq!(combinations(3, 3), [[0, 1, 2]]);
+    }
+
+    #[test]
+    fn bad_share_ids_flags_corrupted_share() {
+        let mut rng = rand::rngs::OsRng;
+        let (secret, prime) = crate::generate_secret_with_rng(&mut rng);
+        let (modulus, mut shares) =
+            crate::split_secret(&secret, &prime, &split_args(5, 3), &mut rng).unwrap();
+        let value = shares[3].value.retrieve().unwrap();
+        shares[3].value =
+            Identifier::new(&value.add_mod(&crypto_bigint::U64::ONE, &prime), modulus);
+
+        // the corrupted share is not part of the working subset
+        let good_shares = shares[..3].to_vec();
+        assert_eq!(bad_share_ids(&good_shares, &shares, modulus).unwrap(), [4]);
+    }
+
+    #[test]
+    fn interpolate_recovers_secret() {
+        let mut rng = rand::rngs::OsRng;
+        let (secret, prime) = crate::generate_secret_with_rng(&mut rng);
+        let (modulus, shares) =
+            crate::split_secret(&secret, &prime, &split_args(5, 3), &mut rng).unwrap();
+        let zero = Identifier::new(&crypto_bigint::U64::ZERO, modulus);
+        assert_eq!(interpolate(&shares[2..], &zero, modulus), Some(secret));
+    }
+
+    #[test]
+    fn typo_candidates_count() {
+        // 16 digits with 15 substitutions each, plus 15 swaps of unequal neighbours
+        assert_eq!(typo_candidates(0x0123_4567_89ab_cdef).len(), 16 * 15 + 15);
+        // only 7 neighbours differ
+        assert_eq!(typo_candidates(0x1122_3344_5566_7788).len(), 16 * 15 + 7);
+        assert_eq!(typo_candidates(0).len(), 16 * 15);
+    }
+
+    #[test]
+    fn typo_candidates_substitution() {
+        let candidates = typo_candidates(0x0123_4567_89ab_cd0f);
+        let (_, typo) = candidates
+            .iter()
+            .find(|(value, _)| *value == 0x0123_4567_89ab_cdef)
+            .expect("correct value is a candidate");
+        assert!(matches!(
+            typo,
+            Typo::Substitution {
+                position: 14,
+                entered: '0',
+                correct: 'e',
+            }
+        ));
+        assert_eq!(typo.to_string(), "digit 15 is 'e' instead of '0'");
+    }
+
+    #[test]
+    fn typo_candidates_transposition() {
+        let candidates = typo_candidates(0x1023_4567_89ab_cdef);
+        let (_, typo) = candidates
+            .iter()
+            .find(|(value, _)| *value == 0x0123_4567_89ab_cdef)
+            .expect("correct value is a candidate");
+        assert!(matches!(typo, Typo::Transposition { position: 0 }));
+        assert_eq!(typo.to_string(), "digits 1 and 2 are swapped");
+    }
+
+    #[test]
+    fn typo_candidates_are_unique() {
+        let candidates = typo_candidates(0x0123_4567_89ab_cdef);
+        let mut values = candidates
+            .iter()
+            .map(|(value, _)| *value)
+            .collect::<Vec<_>>();
+        values.sort_unstable();
+        values.dedup();
+        assert_eq!(values.len(), candidates.len());
+        assert!(!values.contains(&0x0123_4567_89ab_cdef));
+    }
+}
@@ -0,0 +1,87 @@
+//! names of the custodians holding the shares.
+//!
+//! a roster file has one name (or role) per line, the first one holds share id 1, the second share id 2 and so on.
+//! empty lines and lines starting with `#` are ignored.
+
+use std::path::Path;
+
+pub type Roster = Vec<String>;
+
+pub fn read_roster(file: &Path) -> anyhow::Result<Roster> {
+    let content = std::fs::read_to_string(file)
+        .map_err(|e| anyhow::anyhow!("failed to read roster {}: {e}", file.display()))?;
+    parse_roster(&content)
+}
+
+fn parse_roster(content: &str) -> anyhow::Result<Roster> {
+    let roster = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect::<Roster>();
+    // names are looked up case insensitively, so they have to differ in more than case
+    for (i, name) in roster.iter().enumerate() {
+        if roster[..i]
+            .iter()
+            .any(|other| other.eq_ignore_ascii_case(name))
+        {
+            anyhow::bail!("{name} is listed more than once in the roster!");
+        }
+    }
+    Ok(roster)
+}
+
+pub fn name(roster: Option<&[String]>, id: u64) -> Option<&str> {
+    let index = usize::try_from(id).ok()?.checked_sub(1)?;
+    roster?.get(index).map(String::as_str)
+}
+
+// the share id of the custodian with the given name
+pub fn id(roster: Option<&[String]>, name: &str) -> Option<u64> {
+    let index = roster?
+        .iter()
+        .position(|other| other.eq_ignore_ascii_case(name.trim()))?;
+    Some(index as u64 + 1)
+}
+
+// "1 (alice)", or just "1" without a roster
+pub fn describe(roster: Option<&[String]>, id: u64) -> String {
+    match name(roster, id) {
+        Some(name) => format!("{id} ({name})"),
+        None => id.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_and_blank_lines() {
+        let roster = parse_roster("# custodians\n\nalice\n  bob  \n\n# backup\ncarol\n").unwrap();
+        assert_eq!(roster, ["alice", "bob", "carol"]);
+        assert_eq!(name(Some(&roster), 2), Some("bob"));
+        assert_eq!(name(Some(&roster), 0), None);
+        assert_eq!(name(Some(&roster), 4), None);
+        assert_eq!(name(None, 1), None);
+    }
+
+    #[test]
+    fn duplicate_names() {
+        assert!(parse_roster("alice\nbob\nalice\n").is_err());
+        let err = parse_roster("alice\nbob\nAlice\n").unwrap_err();
+        assert!(err.to_string().contains("Alice"), "{err}");
+    }
+
+    #[test]
+    fn case_insensitive_lookup() {
+        let roster = parse_roster("Alice\nbob\n").unwrap();
+        assert_eq!(id(Some(&roster), "alice"), Some(1));
+        assert_eq!(id(Some(&roster), " BOB "), Some(2));
+        assert_eq!(id(Some(&roster), "carol"), None);
+        assert_eq!(id(None, "alice"), None);
+        assert_eq!(describe(Some(&roster), 2), "2 (bob)");
+        assert_eq!(describe(None, 2), "2");
+    }
+}
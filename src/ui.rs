@@ -1,25 +1,57 @@
-use std::io::Write;
+use std::{
+    io::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
+use age::secrecy::SecretString;
 use crypto_bigint::ArrayEncoding;
 
-use crate::{Identifier, U64Modulus, U64Share};
+use crate::{
+    args::{ShareEncoding, SplitArgs},
+    dkek::KdfProgress,
+    Identifier, U64Modulus, U64Share,
+};
 
-pub fn init_term() {
-    let hook = std::panic::take_hook();
-    std::panic::set_hook(Box::new(move |info| {
-        restore_term();
-        hook(info);
-    }));
+const PROGRESS_BAR_WIDTH: usize = 40;
 
-    crossterm::terminal::enable_raw_mode().expect("failed to init terminal");
-    crossterm::execute!(std::io::stdout(), crossterm::terminal::EnterAlternateScreen)
-        .expect("failed to init terminal");
+// the terminal ui is drawn on stderr, stdout is left for documents (like `--shares-output -`).
+// without the terminal ui (shares read from files), messages are just printed and nothing waits for enter.
+// prompts that can't be skipped (like passphrases) set the ui up just for themselves, see `Prompt`
+static TERMINAL_UI: AtomicBool = AtomicBool::new(false);
+
+pub fn init_term() -> anyhow::Result<()> {
+    static PANIC_HOOK: std::sync::Once = std::sync::Once::new();
+    PANIC_HOOK.call_once(|| {
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if TERMINAL_UI.load(Ordering::Relaxed) {
+                restore_term();
+            }
+            hook(info);
+        }));
+    });
+
+    crossterm::terminal::enable_raw_mode()
+        .map_err(|e| anyhow::anyhow!("failed to init terminal: {e}"))?;
+    TERMINAL_UI.store(true, Ordering::Relaxed);
+    crossterm::execute!(
+        std::io::stderr(),
+        crossterm::terminal::EnterAlternateScreen,
+        crossterm::event::EnableBracketedPaste
+    )
+    .map_err(|e| {
+        restore_term();
+        anyhow::anyhow!("failed to init terminal: {e}")
+    })
 }
 
 pub fn restore_term() {
-    if let Err(e) =
-        crossterm::execute!(std::io::stdout(), crossterm::terminal::LeaveAlternateScreen)
-    {
+    TERMINAL_UI.store(false, Ordering::Relaxed);
+    if let Err(e) = crossterm::execute!(
+        std::io::stderr(),
+        crossterm::event::DisableBracketedPaste,
+        crossterm::terminal::LeaveAlternateScreen
+    ) {
         eprintln!("failed to restore terminal: {e}");
     }
     if let Err(e) = crossterm::terminal::disable_raw_mode() {
@@ -27,9 +59,36 @@ pub fn restore_term() {
     }
 }
 
+// sets the terminal ui up for a prompt if it isn't already, and restores the terminal again when dropped
+struct Prompt {
+    restore: bool,
+}
+
+impl Prompt {
+    fn start() -> anyhow::Result<Self> {
+        if TERMINAL_UI.load(Ordering::Relaxed) {
+            return Ok(Self { restore: false });
+        }
+        init_term()?;
+        Ok(Self { restore: true })
+    }
+}
+
+impl Drop for Prompt {
+    fn drop(&mut self) {
+        if self.restore {
+            restore_term();
+        }
+    }
+}
+
 struct Input {
     text: String,
     char_index: usize,
+    // show `*` instead of the entered text
+    masked: bool,
+    // a masked text is shown while this is toggled with tab
+    revealed: bool,
 }
 
 impl Input {
@@ -37,6 +96,8 @@ impl Input {
         Self {
             text: String::new(),
             char_index: 0,
+            masked: false,
+            revealed: false,
         }
     }
 
@@ -57,10 +118,25 @@ impl Input {
         self.char_index = pos.clamp(0, self.text.chars().count());
     }
 
+    // a pasted block of multiple lines is returned as is, see `input::parse_printout`
     fn event_loop(mut self) -> anyhow::Result<String> {
         loop {
-            let crossterm::event::Event::Key(key) = crossterm::event::read()? else {
-                continue;
+            let key = match crossterm::event::read()? {
+                crossterm::event::Event::Key(key) => key,
+                crossterm::event::Event::Paste(text) if text.contains(['\n', '\r']) => {
+                    eprintln!("\r");
+                    return Ok(text.replace("\r\n", "\n").replace('\r', "\n"));
+                }
+                crossterm::event::Event::Paste(text) => {
+                    let prev_cursor = self.char_index;
+                    for c in text.chars().filter(|c| !c.is_control()) {
+                        self.text.insert(self.byte_index(), c);
+                        self.cursor_right();
+                    }
+                    self.redraw(prev_cursor)?;
+                    continue;
+                }
+                _ => continue,
             };
             if key.kind != crossterm::event::KeyEventKind::Press {
                 continue;
@@ -88,142 +164,703 @@ impl Input {
                 }
                 crossterm::event::KeyCode::End => self.char_index = self.text.chars().count(),
                 crossterm::event::KeyCode::Home => self.char_index = 0,
-                crossterm::event::KeyCode::Enter => return Ok(self.text),
+                crossterm::event::KeyCode::Tab if self.masked => self.revealed = !self.revealed,
+                crossterm::event::KeyCode::Enter => {
+                    // don't leave a revealed text on the screen
+                    if self.revealed {
+                        self.revealed = false;
+                        self.redraw(prev_cursor)?;
+                    }
+                    return Ok(self.text);
+                }
                 _ => (),
             }
 
-            if prev_cursor > 0 {
-                crossterm::execute!(
-                    std::io::stdout(),
-                    crossterm::cursor::MoveLeft(prev_cursor as _)
-                )?;
-            }
+            self.redraw(prev_cursor)?;
+        }
+    }
+
+    fn redraw(&self, prev_cursor: usize) -> anyhow::Result<()> {
+        if prev_cursor > 0 {
             crossterm::execute!(
-                std::io::stdout(),
-                crossterm::terminal::Clear(crossterm::terminal::ClearType::UntilNewLine)
+                std::io::stderr(),
+                crossterm::cursor::MoveLeft(prev_cursor as _)
             )?;
-            print!("{}", self.text);
-            let cursor_delta = self.text.chars().count() - self.char_index;
-            if cursor_delta > 0 {
-                crossterm::execute!(
-                    std::io::stdout(),
-                    crossterm::cursor::MoveLeft(cursor_delta as _)
-                )?;
-            }
-            std::io::stdout().flush()?;
         }
+        crossterm::execute!(
+            std::io::stderr(),
+            crossterm::terminal::Clear(crossterm::terminal::ClearType::UntilNewLine)
+        )?;
+        if self.masked && !self.revealed {
+            eprint!("{}", "*".repeat(self.text.chars().count()));
+        } else {
+            eprint!("{}", self.text);
+        }
+        let cursor_delta = self.text.chars().count() - self.char_index;
+        if cursor_delta > 0 {
+            crossterm::execute!(
+                std::io::stderr(),
+                crossterm::cursor::MoveLeft(cursor_delta as _)
+            )?;
+        }
+        std::io::stderr().flush()?;
+        Ok(())
     }
 
     pub fn do_input() -> anyhow::Result<String> {
         Self::new().event_loop()
     }
+
+    pub fn do_masked_input() -> anyhow::Result<String> {
+        Self {
+            masked: true,
+            ..Self::new()
+        }
+        .event_loop()
+    }
 }
 
 fn clear_window() -> anyhow::Result<()> {
-    crossterm::execute!(std::io::stdout(), crossterm::cursor::MoveTo(0, 0))?;
+    crossterm::execute!(std::io::stderr(), crossterm::cursor::MoveTo(0, 0))?;
     crossterm::execute!(
-        std::io::stdout(),
+        std::io::stderr(),
         crossterm::terminal::Clear(crossterm::terminal::ClearType::All)
     )?;
     Ok(())
 }
 
-fn wait_for_enter() -> anyhow::Result<()> {
+// wait until enter (`None`) or one of the given characters is pressed
+fn wait_for_key(chars: &[char]) -> anyhow::Result<Option<char>> {
     loop {
-        match crossterm::event::read()? {
-            crossterm::event::Event::Key(crossterm::event::KeyEvent {
-                code: crossterm::event::KeyCode::Enter,
-                kind: crossterm::event::KeyEventKind::Press,
-                ..
-            }) => return Ok(()),
+        let crossterm::event::Event::Key(key) = crossterm::event::read()? else {
+            continue;
+        };
+        if key.kind != crossterm::event::KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            crossterm::event::KeyCode::Enter => return Ok(None),
+            crossterm::event::KeyCode::Char(c) if chars.contains(&c) => return Ok(Some(c)),
             _ => (),
         }
     }
 }
 
-pub fn get_shares(num_shares: usize) -> anyhow::Result<(U64Modulus, Vec<U64Share>)> {
+fn wait_for_enter() -> anyhow::Result<()> {
+    loop {
+        if let crossterm::event::Event::Key(crossterm::event::KeyEvent {
+            code: crossterm::event::KeyCode::Enter,
+            kind: crossterm::event::KeyEventKind::Press,
+            ..
+        }) = crossterm::event::read()?
+        {
+            return Ok(());
+        }
+    }
+}
+
+// at least `num_shares` shares are requested, but more can be entered to be able to find wrong ones later.
+// share printouts of sc-hsm-tool can be pasted instead of typing the values
+// with a roster, custodians can be entered by name instead of their share id
+// entered shares can be deleted or entered again until the user continues
+pub fn get_shares(
+    num_shares: usize,
+    roster: Option<&[String]>,
+) -> anyhow::Result<(U64Modulus, Vec<U64Share>)> {
+    let _prompt = Prompt::start()?;
     let mut shares = Vec::with_capacity(num_shares);
 
     let mut err = None;
-    let modulus = loop {
+    let (modulus, pasted_shares) = loop {
         clear_window()?;
         if let Some(err) = err {
-            println!("entered prime is invalid! {err:?}\r\nplease try again\r\n");
+            eprintln!("entered prime is invalid! {err}\r");
+            if let InputValidationError::NotPrime = err {
+                eprintln!(
+                    "the shares were created with a prime, so there has to be a typo in it\r"
+                );
+            }
+            eprintln!("please try again\r\n");
         }
-        print!("enter public prime: ");
-        std::io::stdout().flush()?;
+        eprint!("enter public prime (or paste a share printout): ");
+        std::io::stderr().flush()?;
         let input = Input::do_input()?;
-        let modulus = parse_hex_string(&input);
-        match modulus {
-            Ok(modulus) => break U64Modulus::new(&modulus),
+        if crate::input::is_printout(&input) {
+            match crate::input::parse_printout(&input) {
+                Ok((prime, pasted_shares)) => match validate_prime(&prime)
+                    .and_then(|()| validate_shares(&prime, &pasted_shares))
+                {
+                    Ok(()) => break (U64Modulus::new(&prime), pasted_shares),
+                    Err(e) => err = Some(e),
+                },
+                Err(_) => err = Some(InputValidationError::InvalidPrintout),
+            }
+            continue;
+        }
+        match parse_value(&input).and_then(|prime| validate_prime(&prime).map(|()| prime)) {
+            Ok(modulus) => break (U64Modulus::new(&modulus), Vec::new()),
             Err(e) => err = Some(e),
         }
     };
+    let to_share = |(id, value): (u64, crypto_bigint::U64)| U64Share {
+        identifier: Identifier::new(&crypto_bigint::U64::from_u64(id), modulus),
+        value: Identifier::new(&value, modulus),
+    };
+    shares.extend(pasted_shares.into_iter().map(to_share));
 
-    for _ in 0..num_shares {
+    loop {
         clear_window()?;
-        println!("press enter when ready to input the next share\r");
-        wait_for_enter()?;
-        let mut err = None;
-        let share = loop {
-            clear_window()?;
-            if let Some(err) = err {
-                println!("entered share is invalid! {err:?}\r\nplease try again\r\n");
-            }
-            print!("share id   : ");
-            std::io::stdout().flush()?;
-            let share_id = Input::do_input()?;
-            print!("\r\nshare value: ");
-            std::io::stdout().flush()?;
-            let share_value = Input::do_input()?;
-
-            let id = share_id
-                .parse::<u64>()
-                .map_err(|_| InputValidationError::InvalidInteger);
-            let value = parse_hex_string(&share_value);
-
-            match (id, value) {
-                (Ok(id), Ok(value)) => {
-                    break U64Share {
-                        identifier: Identifier::new(&crypto_bigint::U64::from_u64(id), modulus),
-                        value: Identifier::new(&value, modulus),
-                    }
+        let entered = shares
+            .iter()
+            .map(|share| Ok(share.identifier.retrieve()?.as_words()[0]))
+            .collect::<Result<Vec<_>, crate::error::Error>>()?;
+        if let Some(roster) = roster {
+            eprintln!("custodians:\r");
+            for (id, name) in (1..).zip(roster) {
+                if entered.contains(&id) {
+                    eprintln!("  {id:>3} {name:<30} entered\r");
+                } else {
+                    eprintln!("  {id:>3} {name}\r");
                 }
-                (Err(e), _) | (_, Err(e)) => err = Some(e),
             }
+            eprintln!("\r");
+        }
+        if !shares.is_empty() {
+            eprintln!("entered shares:\r");
+            for &id in &entered {
+                eprintln!(
+                    "  share id {:<20} value {}\r",
+                    crate::roster::describe(roster, id),
+                    "*".repeat(16)
+                );
+            }
+            eprintln!("\r");
+        }
+        // custodians are asked in roster order, skipping the ones who already entered their share
+        let next = roster.and_then(|roster| {
+            (1..)
+                .zip(roster)
+                .find(|(id, _)| !entered.contains(id))
+                .map(|(_, name)| name)
+        });
+        if shares.len() < num_shares {
+            match next {
+                Some(name) => eprintln!("press enter when {name} is ready to input their share\r"),
+                None => {
+                    eprintln!("press enter when the next custodian is ready to input their share\r")
+                }
+            }
+        } else {
+            eprintln!("{} shares entered\r", shares.len());
+            eprintln!("press enter to continue or 'a' to input another share\r");
+        }
+        if !shares.is_empty() {
+            eprintln!("press 'd' to delete or 'e' to re-enter one of the entered shares\r");
+        }
+        let choices: &[char] = match (shares.len() < num_shares, shares.is_empty()) {
+            (_, true) => &[],
+            (true, false) => &['d', 'e'],
+            (false, false) => &['a', 'd', 'e'],
         };
-        shares.push(share);
+        match wait_for_key(choices)? {
+            None if shares.len() < num_shares => {
+                shares.push(to_share(read_share(modulus, roster, &entered)?))
+            }
+            None => break,
+            Some('a') => shares.push(to_share(read_share(modulus, roster, &entered)?)),
+            Some('d') => {
+                let index = select_share("enter the id of the share to delete", &shares, roster)?;
+                shares.remove(index);
+            }
+            Some(_) => {
+                let index = select_share("enter the id of the share to re-enter", &shares, roster)?;
+                // the share may get the same id again
+                let others = [&entered[..index], &entered[index + 1..]].concat();
+                shares[index] = to_share(read_share(modulus, roster, &others)?);
+            }
+        }
     }
     clear_window()?;
 
     Ok((modulus, shares))
 }
 
-pub fn print_shares(modulus: &crypto_bigint::U64, shares: &[U64Share]) -> anyhow::Result<()> {
+// a single share, typed in or pasted as printout. its id must not be one of the `entered` ones
+fn read_share(
+    modulus: U64Modulus,
+    roster: Option<&[String]>,
+    entered: &[u64],
+) -> anyhow::Result<(u64, crypto_bigint::U64)> {
+    let mut err = None;
+    loop {
+        clear_window()?;
+        if let Some(err) = err {
+            eprintln!("entered share is invalid! {err}\r\nplease try again\r\n");
+        }
+        eprintln!("leave the checksum empty if the share has none\r");
+        eprintln!("the share value is hidden while typing, press tab to show or hide it\r\n");
+        if roster.is_some() {
+            eprint!("custodian  : ");
+        } else {
+            eprint!("share id   : ");
+        }
+        std::io::stderr().flush()?;
+        let mut share_id = Input::do_input()?;
+        let parsed = if crate::input::is_printout(&share_id) {
+            parse_pasted_share(&share_id, modulus)
+        } else {
+            if let Some(id) = crate::roster::id(roster, &share_id) {
+                eprint!("\r\nshare id   : {id}");
+                share_id = id.to_string();
+            } else if let Some(name) = share_id
+                .parse()
+                .ok()
+                .and_then(|id| crate::roster::name(roster, id))
+            {
+                eprint!("\r\ncustodian  : {name}");
+            }
+            eprint!("\r\nshare value: ");
+            std::io::stderr().flush()?;
+            let share_value = Input::do_masked_input()?;
+            if crate::input::is_printout(&share_value) {
+                parse_pasted_share(&share_value, modulus)
+            } else {
+                eprint!("\r\nchecksum   : ");
+                std::io::stderr().flush()?;
+                let checksum = Input::do_input()?;
+                parse_typed_share(&share_id, &share_value, &checksum, modulus)
+            }
+        };
+
+        let validated = parsed.and_then(|(id, value)| {
+            if entered.contains(&id) {
+                return Err(InputValidationError::DuplicateShareId);
+            }
+            validate_share(modulus.modulus(), id, &value)?;
+            Ok((id, value))
+        });
+        match validated {
+            Ok(share) => return Ok(share),
+            Err(e) => err = Some(e),
+        }
+    }
+}
+
+fn parse_typed_share(
+    id: &str,
+    value: &str,
+    checksum: &str,
+    modulus: U64Modulus,
+) -> Result<(u64, crypto_bigint::U64), InputValidationError> {
+    let id = id
+        .parse::<u64>()
+        .map_err(|_| InputValidationError::InvalidInteger)?;
+    let value = parse_value(value)?;
+    // shares of sc-hsm-tool have no checksum, so it can be left empty
+    if !checksum.trim().is_empty() {
+        crate::checksum::verify(modulus.modulus(), id, &value, checksum)?;
+    }
+    Ok((id, value))
+}
+
+// the shares can't be combined modulo anything but a prime (and `U64Modulus` requires an odd one)
+pub fn validate_prime(prime: &crypto_bigint::U64) -> Result<(), InputValidationError> {
+    if prime.as_words()[0] & 1 == 1 && crypto_primes::is_prime(prime) {
+        Ok(())
+    } else {
+        Err(InputValidationError::NotPrime)
+    }
+}
+
+// shares are only meaningful modulo the prime, and the secret is the value at id 0
+pub fn validate_share(
+    prime: &crypto_bigint::U64,
+    id: u64,
+    value: &crypto_bigint::U64,
+) -> Result<(), InputValidationError> {
+    if id == 0 {
+        return Err(InputValidationError::ShareIdZero);
+    }
+    if crypto_bigint::U64::from_u64(id) >= *prime {
+        return Err(InputValidationError::ShareIdNotBelowPrime);
+    }
+    if value >= prime {
+        return Err(InputValidationError::ShareValueNotBelowPrime);
+    }
+    Ok(())
+}
+
+// validate all shares, which must have distinct ids
+pub fn validate_shares(
+    prime: &crypto_bigint::U64,
+    shares: &[(u64, crypto_bigint::U64)],
+) -> Result<(), InputValidationError> {
+    for (i, (id, value)) in shares.iter().enumerate() {
+        if shares[..i].iter().any(|(other, _)| other == id) {
+            return Err(InputValidationError::DuplicateShareId);
+        }
+        validate_share(prime, *id, value)?;
+    }
+    Ok(())
+}
+
+fn parse_pasted_share(
+    text: &str,
+    modulus: U64Modulus,
+) -> Result<(u64, crypto_bigint::U64), InputValidationError> {
+    let (prime, shares) =
+        crate::input::parse_printout(text).map_err(|_| InputValidationError::InvalidPrintout)?;
+    if prime != *modulus.modulus() {
+        return Err(InputValidationError::PrimeMismatch);
+    }
+    match shares[..] {
+        [share] => Ok(share),
+        _ => Err(InputValidationError::NotExactlyOneShare),
+    }
+}
+
+// let the user pick one of the entered shares by its id (or the name of its custodian), returns the index of the share
+pub fn select_share(
+    message: &str,
+    shares: &[U64Share],
+    roster: Option<&[String]>,
+) -> anyhow::Result<usize> {
+    let _prompt = Prompt::start()?;
+    let mut err = None;
+    loop {
+        clear_window()?;
+        for line in message.lines() {
+            eprintln!("{line}\r");
+        }
+        eprintln!("\r");
+        if let Some(err) = err {
+            eprintln!("entered share id is invalid! {err}\r\nplease try again\r\n");
+        }
+        if roster.is_some() {
+            eprint!("custodian : ");
+        } else {
+            eprint!("share id : ");
+        }
+        std::io::stderr().flush()?;
+        let input = Input::do_input()?;
+        eprintln!("\r");
+        let id = crate::roster::id(roster, &input).map_or_else(|| input.trim().parse::<u64>(), Ok);
+        match id {
+            Ok(id) => match shares.iter().position(|share| {
+                share
+                    .identifier
+                    .retrieve()
+                    .is_ok_and(|i| i.as_words()[0] == id)
+            }) {
+                Some(index) => return Ok(index),
+                None => err = Some(InputValidationError::UnknownShare),
+            },
+            // with a roster, anything that isn't an id is taken as an unknown name
+            Err(_) if roster.is_some() => err = Some(InputValidationError::UnknownShare),
+            Err(_) => err = Some(InputValidationError::InvalidInteger),
+        }
+    }
+}
+
+pub fn print_shares(
+    modulus: &crypto_bigint::U64,
+    shares: &[U64Share],
+    split: &SplitArgs,
+) -> anyhow::Result<()> {
+    let _prompt = Prompt::start()?;
     for share in shares {
+        let id = share.identifier.retrieve()?.as_words()[0];
+        let name = crate::roster::name(split.roster.as_deref(), id);
         clear_window()?;
-        println!("press enter when ready to print the next share\r");
+        match name {
+            Some(name) => {
+                eprintln!("hand the screen to {name} and press enter to print their share\r")
+            }
+            None => eprintln!("press enter when ready to print the next share\r"),
+        }
         wait_for_enter()?;
+        loop {
+            print_share(modulus, share, split, name)?;
+            if !split.confirm_shares || confirm_share(modulus, share)? {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_share(
+    modulus: &crypto_bigint::U64,
+    share: &U64Share,
+    split: &SplitArgs,
+    name: Option<&str>,
+) -> anyhow::Result<()> {
+    let id = share.identifier.retrieve()?.as_words()[0];
+    let value = share.value.retrieve()?;
+    clear_window()?;
+    if let Some(name) = name {
+        eprintln!("custodian   : {name}\r");
+    }
+    eprintln!(
+        "prime       : {}\r",
+        format_value(modulus, split.share_encoding)
+    );
+    eprintln!("share id    : {}\r", id);
+    eprintln!(
+        "share value : {}\r",
+        format_value(&value, split.share_encoding)
+    );
+    eprintln!(
+        "checksum    : {}\r",
+        crate::checksum::format_checksum(modulus, id, &value)
+    );
+    if split.qr {
+        let code = crate::output::share_qr_code(modulus, share, split)?;
+        eprint!(
+            "\r\n{}\r\n",
+            crate::qr::render_terminal(&code).replace('\n', "\r\n")
+        );
+    }
+    eprintln!("\npress enter to continue\r");
+    wait_for_enter()?;
+    Ok(())
+}
+
+// let the custodian type in what they wrote down, returns whether it matches the share
+fn confirm_share(modulus: &crypto_bigint::U64, share: &U64Share) -> anyhow::Result<bool> {
+    let expected_id = share.identifier.retrieve()?.as_words()[0];
+    let expected_value = share.value.retrieve()?;
+    let mut err = None;
+    let (prime, id, value, checksum_matches) = loop {
         clear_window()?;
-        let id = share.identifier.retrieve().as_words()[0];
-        println!("prime       : {}\r", format_bigint(modulus));
-        println!("share id    : {}\r", id);
-        println!("share value : {}\r", format_bigint(&share.value.retrieve()));
-        println!("\npress enter to continue\r");
-        wait_for_enter()?;
+        eprintln!("enter the share you just wrote down to make sure it was copied correctly\r");
+        eprintln!("the share value is hidden while typing, press tab to show or hide it\r\n");
+        if let Some(err) = err {
+            eprintln!("entered share is invalid! {err}\r\nplease try again\r\n");
+        }
+        eprint!("prime      : ");
+        std::io::stderr().flush()?;
+        let prime = Input::do_input()?;
+        eprint!("\r\nshare id   : ");
+        std::io::stderr().flush()?;
+        let id = Input::do_input()?;
+        eprint!("\r\nshare value: ");
+        std::io::stderr().flush()?;
+        let value = Input::do_masked_input()?;
+        eprint!("\r\nchecksum   : ");
+        std::io::stderr().flush()?;
+        let checksum = Input::do_input()?;
+        let parsed = parse_value(&prime).and_then(|prime| {
+            let id = id
+                .trim()
+                .parse::<u64>()
+                .map_err(|_| InputValidationError::InvalidInteger)?;
+            let value = parse_value(&value)?;
+            // compared against the checksum of the actual share, a malformed checksum is entered again
+            let checksum_matches =
+                match crate::checksum::verify(modulus, expected_id, &expected_value, &checksum) {
+                    Ok(()) => true,
+                    Err(InputValidationError::ChecksumMismatch) => false,
+                    Err(e) => return Err(e),
+                };
+            Ok((prime, id, value, checksum_matches))
+        });
+        match parsed {
+            Ok(parsed) => break parsed,
+            Err(e) => err = Some(e),
+        }
+    };
+
+    let mut mismatches = Vec::new();
+    if prime != *modulus {
+        mismatches.push("prime");
     }
+    if id != expected_id {
+        mismatches.push("share id");
+    }
+    if value != expected_value {
+        mismatches.push("share value");
+    }
+    if !checksum_matches {
+        mismatches.push("checksum");
+    }
+    let mismatches = match mismatches.split_last() {
+        None => return Ok(true),
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} and {last}", rest.join(", ")),
+    };
+    show_message(&format!(
+        "the entered {mismatches} does not match!\n\nthe share is shown again, please correct what you wrote down"
+    ))?;
+    Ok(false)
+}
+
+pub fn get_passphrase(message: &str) -> anyhow::Result<SecretString> {
+    let _prompt = Prompt::start()?;
+    clear_window()?;
+    eprintln!("{message}\r\n");
+    eprint!("passphrase : ");
+    std::io::stderr().flush()?;
+    let passphrase = Input::do_masked_input()?;
+    eprintln!("\r");
+    Ok(passphrase.into())
+}
+
+// the passphrase has to be entered twice to make sure there is no typo in it
+pub fn get_new_passphrase(message: &str) -> anyhow::Result<SecretString> {
+    let _prompt = Prompt::start()?;
+    let mut err = None;
+    loop {
+        clear_window()?;
+        eprintln!("{message}\r\n");
+        if let Some(err) = err {
+            eprintln!("{err}\r\nplease try again\r\n");
+        }
+        eprint!("passphrase        : ");
+        std::io::stderr().flush()?;
+        let passphrase = Input::do_masked_input()?;
+        eprint!("\r\nrepeat passphrase : ");
+        std::io::stderr().flush()?;
+        let repeated = Input::do_masked_input()?;
+        eprintln!("\r");
+        if passphrase.is_empty() {
+            err = Some("the passphrase must not be empty!");
+        } else if passphrase != repeated {
+            err = Some("the passphrases don't match!");
+        } else {
+            return Ok(passphrase.into());
+        }
+    }
+}
+
+// run `f` on a worker thread while showing its kdf progress. ctrl+c cancels the kdf
+pub fn run_with_progress<R: Send>(f: impl FnOnce(&KdfProgress) -> R + Send) -> anyhow::Result<R> {
+    let progress = KdfProgress::default();
+    if !TERMINAL_UI.load(Ordering::Relaxed) {
+        return Ok(f(&progress));
+    }
+    let result = std::thread::scope(|scope| {
+        let worker = scope.spawn(|| f(&progress));
+        let result = progress_loop(&progress, || worker.is_finished());
+        if result.is_err() {
+            // the worker has to stop before the scope can be left
+            progress.cancel();
+        }
+        let value = worker
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        result.map(|()| value)
+    });
+    eprintln!("\r");
+    if progress.is_cancelled() {
+        return Err(crate::dkek::Cancelled.into());
+    }
+    result
+}
+
+fn progress_loop(progress: &KdfProgress, is_finished: impl Fn() -> bool) -> anyhow::Result<()> {
+    while !is_finished() {
+        if crossterm::event::poll(std::time::Duration::from_millis(100))? {
+            if let crossterm::event::Event::Key(crossterm::event::KeyEvent {
+                code: crossterm::event::KeyCode::Char('c'),
+                modifiers: crossterm::event::KeyModifiers::CONTROL,
+                kind: crossterm::event::KeyEventKind::Press,
+                ..
+            }) = crossterm::event::read()?
+            {
+                progress.cancel();
+            }
+        }
+
+        let fraction = progress.fraction();
+        let filled = (fraction * PROGRESS_BAR_WIDTH as f64) as usize;
+        crossterm::execute!(
+            std::io::stderr(),
+            crossterm::cursor::MoveToColumn(0),
+            crossterm::terminal::Clear(crossterm::terminal::ClearType::CurrentLine)
+        )?;
+        eprint!(
+            "{} [{}{}] {:3.0}% (ctrl+c to cancel)",
+            progress.message(),
+            "#".repeat(filled),
+            "-".repeat(PROGRESS_BAR_WIDTH - filled),
+            fraction * 100.0
+        );
+        std::io::stderr().flush()?;
+    }
+    Ok(())
+}
+
+pub fn show_message(message: &str) -> anyhow::Result<()> {
+    if !TERMINAL_UI.load(Ordering::Relaxed) {
+        eprintln!("{message}\n");
+        return Ok(());
+    }
+    clear_window()?;
+    for line in message.lines() {
+        eprintln!("{line}\r");
+    }
+    eprintln!("\npress enter to continue\r");
+    wait_for_enter()?;
+    clear_window()?;
     Ok(())
 }
 
 #[derive(Debug)]
-enum InputValidationError {
+pub enum InputValidationError {
     InvalidInteger,
+    UnknownShare,
+    InvalidPrintout,
+    PrimeMismatch,
+    NotPrime,
+    NotExactlyOneShare,
+    ShareIdZero,
+    ShareIdNotBelowPrime,
+    DuplicateShareId,
+    ShareValueNotBelowPrime,
+    ChecksumMismatch,
+    UnknownWord,
     InputNotHex,
     WrongLength,
     Other,
 }
 
-fn parse_hex_string<S: AsRef<str>>(input: S) -> Result<crypto_bigint::U64, InputValidationError> {
+impl std::fmt::Display for InputValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::InvalidInteger => "the share id has to be a number",
+            Self::UnknownShare => "there is no share with this share id or custodian",
+            Self::InvalidPrintout => "the pasted share printout could not be read",
+            Self::PrimeMismatch => "the prime of the pasted share differs from the prime entered before",
+            Self::NotPrime => "the prime is not a prime number",
+            Self::NotExactlyOneShare => "the pasted share printout has to contain exactly one share",
+            Self::ShareIdZero => "the share id must not be 0",
+            Self::ShareIdNotBelowPrime => "the share id has to be smaller than the prime",
+            Self::DuplicateShareId => "a share with this share id was already entered",
+            Self::ShareValueNotBelowPrime => "the share value has to be smaller than the prime",
+            Self::ChecksumMismatch => "the checksum does not match the share id and share value",
+            Self::UnknownWord => "the share value contains a word that is not in the word list",
+            Self::InputNotHex => "the value may only contain hex digits and colons",
+            Self::WrongLength => {
+                "the value has the wrong length, primes and share values have 16 hex digits or 7 words, checksums 4 hex digits"
+            }
+            Self::Other => "the value is not a valid hex number",
+        })
+    }
+}
+
+// values can be entered as hex or as words, see `crate::words`
+fn parse_value(input: &str) -> Result<crypto_bigint::U64, InputValidationError> {
+    if input.split_whitespace().nth(1).is_some() {
+        crate::words::parse_words(input)
+    } else {
+        parse_hex_string(input.trim())
+    }
+}
+
+pub fn parse_hex_string<S: AsRef<str>>(
+    input: S,
+) -> Result<crypto_bigint::U64, InputValidationError> {
     let input = input.as_ref();
     if input.chars().any(|c| !c.is_ascii_hexdigit() && c != ':') {
         return Err(InputValidationError::InputNotHex);
@@ -241,15 +878,155 @@ fn parse_hex_string<S: AsRef<str>>(input: S) -> Result<crypto_bigint::U64, Input
     let result = std::panic::catch_unwind(move || crypto_bigint::U64::from_be_hex(&input))
         .map_err(|_| InputValidationError::Other);
     std::panic::set_hook(hook);
-    return result;
+    result
+}
+
+pub fn format_value(value: &crypto_bigint::U64, encoding: ShareEncoding) -> String {
+    match encoding {
+        ShareEncoding::Hex => format_bigint(value),
+        ShareEncoding::Words => crate::words::format_words(value),
+    }
 }
 
-fn format_bigint(val: &crypto_bigint::U64) -> String {
-    let bytes: &[u8] = &*val.to_be_byte_array();
+pub fn format_bigint(val: &crypto_bigint::U64) -> String {
+    format_bytes(&val.to_be_byte_array())
+}
+
+pub fn format_bytes(bytes: &[u8]) -> String {
     bytes
-        .into_iter()
+        .iter()
         .map(|b| format!("{:02x}:", b))
         .collect::<String>()
         .trim_end_matches(':')
         .to_owned()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRINTOUT: &str = "\
+Share 1 of 3
+
+Prime       : BC:76:8F:0F:E2:33:65:29
+Share ID    : 1
+Share value : 62:90:69:DA:90:78:72:E0
+";
+
+    #[test]
+    fn pasted_share_with_entered_prime() {
+        let modulus = U64Modulus::new(&crypto_bigint::U64::from_u64(0xbc76_8f0f_e233_6529));
+        let (id, value) = parse_pasted_share(PRINTOUT, modulus).unwrap();
+        assert_eq!(id, 1);
+        assert_eq!(value, crypto_bigint::U64::from_u64(0x6290_69da_9078_72e0));
+    }
+
+    #[test]
+    fn pasted_share_with_other_prime() {
+        let modulus = U64Modulus::new(&crypto_bigint::U64::from_u64(0xffff_ffff_ffff_ffc5));
+        assert!(matches!(
+            parse_pasted_share(PRINTOUT, modulus),
+            Err(InputValidationError::PrimeMismatch)
+        ));
+    }
+
+    #[test]
+    fn pasted_share_with_multiple_shares() {
+        let modulus = U64Modulus::new(&crypto_bigint::U64::from_u64(0xbc76_8f0f_e233_6529));
+        let printouts = format!("{PRINTOUT}\n{}", PRINTOUT.replace("ID    : 1", "ID    : 2"));
+        assert!(matches!(
+            parse_pasted_share(&printouts, modulus),
+            Err(InputValidationError::NotExactlyOneShare)
+        ));
+    }
+
+    // share id 1 of `tests/data/shares.json`
+    const PRIME: u64 = 0xbc76_8f0f_e233_6529;
+    const VALUE: u64 = 0x6290_69da_9078_72e0;
+
+    fn u64(value: u64) -> crypto_bigint::U64 {
+        crypto_bigint::U64::from_u64(value)
+    }
+
+    #[test]
+    fn valid_shares() {
+        assert!(validate_share(&u64(PRIME), 1, &u64(VALUE)).is_ok());
+        assert!(validate_share(&u64(PRIME), PRIME - 1, &u64(PRIME - 1)).is_ok());
+        assert!(validate_shares(&u64(PRIME), &[(1, u64(VALUE)), (2, u64(0))]).is_ok());
+    }
+
+    #[test]
+    fn share_id_zero() {
+        assert!(matches!(
+            validate_share(&u64(PRIME), 0, &u64(VALUE)),
+            Err(InputValidationError::ShareIdZero)
+        ));
+    }
+
+    #[test]
+    fn share_id_not_below_prime() {
+        for id in [PRIME, PRIME + 1, u64::MAX] {
+            assert!(matches!(
+                validate_share(&u64(PRIME), id, &u64(VALUE)),
+                Err(InputValidationError::ShareIdNotBelowPrime)
+            ));
+        }
+    }
+
+    #[test]
+    fn share_value_not_below_prime() {
+        for value in [PRIME, PRIME + 1, u64::MAX] {
+            assert!(matches!(
+                validate_share(&u64(PRIME), 1, &u64(value)),
+                Err(InputValidationError::ShareValueNotBelowPrime)
+            ));
+        }
+    }
+
+    #[test]
+    fn duplicate_share_ids() {
+        let shares = [(1, u64(VALUE)), (2, u64(VALUE)), (1, u64(0))];
+        assert!(matches!(
+            validate_shares(&u64(PRIME), &shares),
+            Err(InputValidationError::DuplicateShareId)
+        ));
+    }
+
+    #[test]
+    fn invalid_share_among_shares() {
+        let shares = [(1, u64(VALUE)), (0, u64(VALUE))];
+        assert!(matches!(
+            validate_shares(&u64(PRIME), &shares),
+            Err(InputValidationError::ShareIdZero)
+        ));
+    }
+
+    #[test]
+    fn prime() {
+        assert!(validate_prime(&u64(PRIME)).is_ok());
+        // the largest 64 bit prime
+        assert!(validate_prime(&u64(0xffff_ffff_ffff_ffc5)).is_ok());
+    }
+
+    #[test]
+    fn even_numbers() {
+        // 2 is a prime, but `U64Modulus` requires an odd one
+        for prime in [0, 2, 4, PRIME + 1, u64::MAX - 1] {
+            assert!(matches!(
+                validate_prime(&u64(prime)),
+                Err(InputValidationError::NotPrime)
+            ));
+        }
+    }
+
+    #[test]
+    fn odd_composite() {
+        // 3 * 5 * 7 * 11 and a product of two 32 bit primes
+        for prime in [1155, 0xffff_fffb * 0xffff_ffbf, u64::MAX] {
+            assert!(matches!(
+                validate_prime(&u64(prime)),
+                Err(InputValidationError::NotPrime)
+            ));
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! 64 bit values (the prime and share values) as words of the bip39 english wordlist, which are easier to
+//! write down and read back than hex. every word encodes 11 bits, the 13 bits left over in the last word are
+//! a checksum of the value, so a wrong or swapped word is noticed when entering it
+
+use crypto_bigint::Encoding;
+
+use crate::ui::InputValidationError;
+
+pub const WORD_COUNT: usize = 7;
+const BITS_PER_WORD: usize = 11;
+const CHECKSUM_BITS: usize = WORD_COUNT * BITS_PER_WORD - 64;
+// the words of the list are unique in their first four letters, so these are enough when entering words
+const MIN_PREFIX_LEN: usize = 4;
+
+fn words_checksum(value: &crypto_bigint::U64) -> u128 {
+    use sha2::Digest;
+    let hash = sha2::Sha256::digest(value.to_be_bytes());
+    (u16::from_be_bytes([hash[0], hash[1]]) >> (16 - CHECKSUM_BITS)).into()
+}
+
+pub fn format_words(value: &crypto_bigint::U64) -> String {
+    let bits = (u128::from(value.as_words()[0]) << CHECKSUM_BITS) | words_checksum(value);
+    let word_list = bip39::Language::English.word_list();
+    (0..WORD_COUNT)
+        .rev()
+        .map(|i| word_list[((bits >> (i * BITS_PER_WORD)) & 0x7ff) as usize])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn parse_words(input: &str) -> Result<crypto_bigint::U64, InputValidationError> {
+    let words = input.split_whitespace().collect::<Vec<_>>();
+    if words.len() != WORD_COUNT {
+        return Err(InputValidationError::WrongLength);
+    }
+    let mut bits = 0u128;
+    for word in words {
+        bits = (bits << BITS_PER_WORD) | u128::from(find_word(&word.to_ascii_lowercase())?);
+    }
+    let value = crypto_bigint::U64::from_u64((bits >> CHECKSUM_BITS) as u64);
+    if bits & ((1 << CHECKSUM_BITS) - 1) != words_checksum(&value) {
+        return Err(InputValidationError::ChecksumMismatch);
+    }
+    Ok(value)
+}
+
+fn find_word(word: &str) -> Result<u16, InputValidationError> {
+    let language = bip39::Language::English;
+    if let Some(index) = language.find_word(word) {
+        return Ok(index);
+    }
+    match language.words_by_prefix(word) {
+        [found] if word.len() >= MIN_PREFIX_LEN => {
+            Ok(language.find_word(found).expect("word is from the list"))
+        }
+        _ => Err(InputValidationError::UnknownWord),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u64(value: u64) -> crypto_bigint::U64 {
+        crypto_bigint::U64::from_u64(value)
+    }
+
+    const VALUES: [u64; 4] = [0, 1, 0x6290_69da_9078_72e0, u64::MAX];
+
+    #[test]
+    fn round_trip() {
+        for value in VALUES {
+            let words = format_words(&u64(value));
+            assert_eq!(words.split(' ').count(), WORD_COUNT);
+            assert_eq!(parse_words(&words).unwrap(), u64(value), "{words}");
+        }
+    }
+
+    #[test]
+    fn round_trip_prefixes() {
+        for value in VALUES {
+            let words = format_words(&u64(value))
+                .split(' ')
+                .map(|word| {
+                    word.chars()
+                        .take(MIN_PREFIX_LEN)
+                        .collect::<String>()
+                        .to_uppercase()
+                })
+                .collect::<Vec<_>>()
+                .join("  ");
+            assert_eq!(parse_words(&words).unwrap(), u64(value), "{words}");
+        }
+    }
+
+    #[test]
+    fn find_by_prefix() {
+        assert_eq!(find_word("abandon").unwrap(), 0);
+        assert_eq!(find_word("aban").unwrap(), 0);
+        assert_eq!(find_word("zoo").unwrap(), 2047);
+        // too short, even if it is unique
+        assert!(matches!(
+            find_word("aba"),
+            Err(InputValidationError::UnknownWord)
+        ));
+        assert!(matches!(
+            find_word("abandoned"),
+            Err(InputValidationError::UnknownWord)
+        ));
+    }
+
+    #[test]
+    fn swapped_words_detected() {
+        let words = format_words(&u64(0x6290_69da_9078_72e0));
+        let mut swapped = words.split(' ').collect::<Vec<_>>();
+        assert_ne!(swapped[1], swapped[2]);
+        swapped.swap(1, 2);
+        assert!(matches!(
+            parse_words(&swapped.join(" ")),
+            Err(InputValidationError::ChecksumMismatch)
+        ));
+    }
+
+    #[test]
+    fn wrong_word_count() {
+        let words = format_words(&u64(1));
+        let (_, fewer) = words.split_once(' ').unwrap();
+        assert!(matches!(
+            parse_words(fewer),
+            Err(InputValidationError::WrongLength)
+        ));
+        assert!(matches!(
+            parse_words(&format!("{words} zoo")),
+            Err(InputValidationError::WrongLength)
+        ));
+    }
+}
@@ -0,0 +1,46 @@
+//! runs `create` with the new shares written to stdout, which needs no terminal at all, and checks that the
+//! written dkek share file can be verified with these shares.
+
+use std::process::{Command, Stdio};
+
+#[test]
+fn create_to_stdout() {
+    let dir =
+        std::env::temp_dir().join(format!("sc-hsm-recrypt-test-{}-create", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output_file = dir.join("created.bin");
+    let shares_file = dir.join("shares.json");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_sc-hsm-recrypt"))
+        .args(["create", "--shares-total", "3", "--shares-required", "2"])
+        .args(["--shares-output", "-", "-o"])
+        .arg(&output_file)
+        .stdin(Stdio::null())
+        .output()
+        .expect("failed to run sc-hsm-recrypt");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    std::fs::write(&shares_file, &output.stdout).unwrap();
+
+    let verified = Command::new(env!("CARGO_BIN_EXE_sc-hsm-recrypt"))
+        .args(["verify", "--shares-required", "2", "-f"])
+        .arg(&output_file)
+        .arg("--shares-from")
+        .arg(&shares_file)
+        .stdin(Stdio::null())
+        .output()
+        .expect("failed to run sc-hsm-recrypt");
+    std::fs::remove_dir_all(&dir).unwrap();
+    let verify_stderr = String::from_utf8_lossy(&verified.stderr);
+    assert!(verified.status.success(), "{verify_stderr}");
+
+    // both show the kcv of the same dkek
+    let document: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("stdout is not json");
+    let kcv = document["dkek_kcv"].as_str().unwrap();
+    assert!(stderr.contains(&format!("dkek kcv : {kcv}")), "{stderr}");
+    assert!(
+        verify_stderr.contains(&format!("dkek kcv : {kcv}")),
+        "{verify_stderr}"
+    );
+}
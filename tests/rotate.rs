@@ -0,0 +1,103 @@
+//! runs `rotate` with shares read from files and the new shares written to stdout, which needs no terminal at all.
+//!
+//! see `verify.rs` for the files in `data`.
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+// a fresh directory for the files written by a test
+fn test_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("sc-hsm-recrypt-test-{}-{name}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn rotate(output_file: &Path, args: &[&str]) -> std::process::Output {
+    let data = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data");
+    Command::new(env!("CARGO_BIN_EXE_sc-hsm-recrypt"))
+        .current_dir(data)
+        .args(["rotate", "-f", "dkek.bin", "--shares-required", "2"])
+        .args(["--shares-total", "3", "--shares-from", "shares.json"])
+        .args(args)
+        .arg("-o")
+        .arg(output_file)
+        .stdin(Stdio::null())
+        .output()
+        .expect("failed to run sc-hsm-recrypt")
+}
+
+#[test]
+fn rotate_to_stdout() {
+    let dir = test_dir("stdout");
+    let output = rotate(&dir.join("rotated.bin"), &["--shares-output", "-"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+
+    // stdout has to be nothing but the document
+    let document: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("stdout is not json");
+    assert_eq!(document["dkek_kcv"], "2b:ed:88:81:78:d5:14:ab");
+    assert_eq!(document["shares"].as_array().map(Vec::len), Some(3));
+}
+
+#[test]
+fn rotate_refuses_existing_qr_code() {
+    let dir = test_dir("qr");
+    std::fs::write(dir.join("share-2.svg"), "").unwrap();
+    let output_file = dir.join("rotated.bin");
+    let output = rotate(
+        &output_file,
+        &["--shares-output", "-", "--qr-output", dir.to_str().unwrap()],
+    );
+    let written = output_file.exists();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{stderr}");
+    assert!(stderr.contains("share-2.svg already exists"), "{stderr}");
+    assert!(!written);
+}
+
+#[test]
+fn rotate_refuses_existing_card() {
+    let dir = test_dir("cards");
+    std::fs::write(dir.join("card-3.html"), "").unwrap();
+    let output_file = dir.join("rotated.bin");
+    let output = rotate(
+        &output_file,
+        &[
+            "--shares-output",
+            "-",
+            "--cards-output",
+            dir.to_str().unwrap(),
+        ],
+    );
+    let written = output_file.exists();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{stderr}");
+    assert!(stderr.contains("card-3.html already exists"), "{stderr}");
+    assert!(!written);
+}
+
+#[test]
+fn rotate_removes_output_when_shares_fail() {
+    let dir = test_dir("cleanup");
+    let output_file = dir.join("rotated.bin");
+    // the directory of the shares output doesn't exist, which is only noticed when the shares are written
+    let shares_output = dir.join("missing").join("shares.json");
+    let output = rotate(
+        &output_file,
+        &["--shares-output", shares_output.to_str().unwrap()],
+    );
+    let written = output_file.exists();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{stderr}");
+    assert!(stderr.contains("was removed again"), "{stderr}");
+    assert!(stderr.contains("shares.json"), "{stderr}");
+    assert!(!written);
+}
@@ -0,0 +1,50 @@
+//! runs `verify` with shares read from files, which must work without a terminal.
+//!
+//! `data/dkek.bin` is a dkek share file created by this tool, `data/shares.json` are its 2 of 3 shares.
+//! `data/wrong-shares.json` has a typo in the last digit of share 2.
+
+use std::process::{Command, Stdio};
+
+fn verify(shares_from: &[&str]) -> std::process::Output {
+    let data = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data");
+    let mut command = Command::new(env!("CARGO_BIN_EXE_sc-hsm-recrypt"));
+    command
+        .current_dir(data)
+        .args(["verify", "-f", "dkek.bin", "--shares-required", "2"]);
+    for file in shares_from {
+        command.args(["--shares-from", file]);
+    }
+    command
+        .stdin(Stdio::null())
+        .output()
+        .expect("failed to run sc-hsm-recrypt")
+}
+
+#[test]
+fn verify_from_share_file() {
+    let output = verify(&["shares.json"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(
+        stderr.contains("dkek kcv : 2b:ed:88:81:78:d5:14:ab"),
+        "{stderr}"
+    );
+    // stdout is kept for documents
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn verify_from_wrong_shares() {
+    let output = verify(&["wrong-shares.json"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{stderr}");
+    assert!(stderr.contains("failed to decrypt"), "{stderr}");
+}
+
+#[test]
+fn verify_from_empty_stdin() {
+    let output = verify(&["-"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{stderr}");
+    assert!(stderr.contains("failed to read shares from -"), "{stderr}");
+}